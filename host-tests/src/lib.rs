@@ -0,0 +1,40 @@
+//! Runs the unit tests of the firmware modules that do not depend on the hardware on the host.
+//! The modules are included from the sources of the firmware with the same hierarchy.
+#![allow(dead_code)]
+
+#[path = "../../src/dns"]
+mod dns {
+    pub mod nat_pmp {
+        pub mod message;
+    }
+    pub mod stun {
+        pub mod message;
+    }
+    pub mod upnp {
+        pub mod description;
+    }
+    pub mod verify {
+        pub mod message;
+    }
+}
+
+#[path = "../../src/http_client"]
+mod http_client {
+    pub mod response;
+}
+
+#[path = "../../src/http_server"]
+mod http_server {
+    pub mod form;
+    pub mod request;
+    pub mod status;
+    pub mod websocket_utils {
+        pub mod protocol;
+    }
+}
+
+#[path = "../../src/utils"]
+mod utils {
+    mod encoding;
+    pub use encoding::{encode_base64, sha1};
+}
@@ -0,0 +1,63 @@
+#!/usr/bin/env sh
+# Check the HTTPS server of a device flashed with the `https` feature.
+# Exits with an error if a TLS handshake or a request fails, so it can run in CI.
+#
+# Usage: scripts/check-https.sh <host> [port] [connections]
+# The certificate is read from HTTPS_CERT_PATH, or from certs/cert.pem by default.
+# `connections` is the number of parallel handshakes, which should be HTTP_MAX_CONNECTIONS.
+set -eu
+
+host="${1:?Usage: $0 <host> [port] [connections]}"
+port="${2:-443}"
+connections="${3:-2}"
+cert="${HTTPS_CERT_PATH:-certs/cert.pem}"
+logs="$(mktemp -d)"
+trap 'rm -rf "$logs"' EXIT
+
+# Perform a handshake and keep the session open for a few seconds
+handshake() {
+    sleep 3 | openssl s_client -connect "$host:$port" -servername "$host" "$1" \
+        -CAfile "$cert" -verify_return_error >"$logs/$2.log" 2>&1
+}
+
+failed=0
+for version in -tls1_2 -tls1_3; do
+    if handshake "$version" "$version"; then
+        echo "ok: $version handshake"
+    else
+        echo "FAILED: $version handshake"
+        tail -n 5 "$logs/$version.log"
+        failed=1
+    fi
+done
+
+# Every connection of the server must be able to hold a session at the same time
+pids=""
+for i in $(seq "$connections"); do
+    handshake -tls1_3 "parallel-$i" &
+    pids="$pids $!"
+done
+i=0
+for pid in $pids; do
+    i=$((i + 1))
+    if wait "$pid"; then
+        echo "ok: parallel handshake $i of $connections"
+    else
+        echo "FAILED: parallel handshake $i of $connections"
+        tail -n 5 "$logs/parallel-$i.log"
+        failed=1
+    fi
+done
+
+# The status endpoint is public unless authentication is enabled, in which case 401 is expected
+status="$(curl --silent --output /dev/null --write-out '%{http_code}' \
+    --cacert "$cert" "https://$host:$port/api/v1/status" || true)"
+case "$status" in
+200 | 401) echo "ok: GET /api/v1/status answered $status" ;;
+*)
+    echo "FAILED: GET /api/v1/status answered '$status'"
+    failed=1
+    ;;
+esac
+
+exit "$failed"
@@ -1,79 +1,210 @@
-use crate::utils::{abort_connection, parse_ip_address, wait_for_connection, write_tcp_buf};
+mod backoff;
+mod nat_pmp;
+mod provider;
+mod public_ip;
+mod published;
+mod stun;
+mod template;
+mod upnp;
+mod verify;
 
-use embassy_net::{IpAddress, IpEndpoint, Stack, dns::DnsQueryType, tcp::TcpSocket};
-use embassy_time::{Duration, Timer};
+use crate::{
+    events::{Event, publish_event},
+    http_client::{Response, Transport, send_request},
+    metrics::update_metrics,
+    state::{DnsUpdate, get_state, update_state},
+    utils::wait_for_connection,
+};
+
+use backoff::Backoff;
+use embassy_net::{IpAddress, Stack};
+use embassy_time::{Duration, Instant, Timer};
 use esp_backtrace as _;
-use heapless::{String, Vec};
+use esp_hal::rng::Rng;
+use heapless::Vec;
+use provider::{PROVIDER, UpdateError};
+use public_ip::{get_public_ip, get_public_ipv6};
+use template::Values;
+#[cfg(feature = "tls")]
+use {
+    crate::tls::{Verification, parse_fingerprint, pem_file},
+    core::ffi::CStr,
+};
 
 /// The interval in seconds between the DNS update checks.
 const DNS_CHECK_DELAY: &str = env!("DNS_CHECK_DELAY");
 /// The fallback interval in seconds between the DNS update checks.
 const DNS_CHECK_DELAY_FALLBACK: u64 = 60;
-/// The hostname of the update service of your DNS provider.
+/// The interval in seconds after which a record is updated even if the address has not changed, or 0 to never force it.
+const DNS_REFRESH_INTERVAL: &str = env!("DNS_REFRESH_INTERVAL");
+/// The fallback interval in seconds after which a record is updated again.
+const DNS_REFRESH_INTERVAL_FALLBACK: u64 = 0;
+/// The time in seconds to wait after an update before checking that the hostname resolves to the new address.
+const DNS_VERIFY_DELAY: &str = env!("DNS_VERIFY_DELAY");
+/// The fallback time in seconds to wait before checking the hostname.
+const DNS_VERIFY_DELAY_FALLBACK: u64 = 300;
+/// The hostname of the update service of your DNS provider, for the custom and dyndns2 providers.
 const DNS_HOST: &str = env!("DNS_HOST");
-/// The HTTP request format for updating the DNS.
-const DNS_HTTP_REQUEST: &[u8] = env!("DNS_HTTP_REQUEST").as_bytes();
+/// The HTTP request format for updating the DNS, for the custom provider.
+/// The `{ip}`, `{ipv6}` and `{hostname}` placeholders are replaced before it is sent.
+const DNS_HTTP_REQUEST: &str = env!("DNS_HTTP_REQUEST");
+/// The HTTP request format for updating the IPv6 record, for the custom provider.
+/// It takes the same placeholders as `DNS_HTTP_REQUEST`.
+const DNS_HTTP_REQUEST_IPV6: &str = env!("DNS_HTTP_REQUEST_IPV6");
+/// The flag enabling the updates of the IPv6 (AAAA) record alongside the IPv4 (A) record.
+const DNS_IPV6_ENABLE: &str = env!("DNS_IPV6_ENABLE");
+
+/// The flag enabling HTTPS for the requests to the DNS provider and the public IP provider.
+#[cfg(feature = "tls")]
+const DNS_TLS_ENABLE: &str = env!("DNS_TLS_ENABLE");
+/// The certificate authorities trusted by the HTTPS client in the PEM format, relative to the root of the project.
+/// It is included in the firmware at build time.
+#[cfg(feature = "tls")]
+const CA_CERTIFICATES: &CStr = pem_file(concat!(
+    include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/",
+        env!("DNS_TLS_CA_PATH")
+    )),
+    "\0"
+));
+/// The SHA-256 fingerprint of the certificate of the DNS provider, replacing the certificate authorities if not empty.
+#[cfg(feature = "tls")]
+const DNS_TLS_PINNED_FINGERPRINT: &str = env!("DNS_TLS_PINNED_FINGERPRINT");
+
+/// The port of the HTTP services.
+const HTTP_PORT: u16 = 80;
+/// The port of the HTTPS services.
+#[cfg(feature = "tls")]
+const HTTPS_PORT: u16 = 443;
+
+/// The user agent sent to the providers, which is required by the dyndns2 protocol.
+const USER_AGENT: &str = concat!("wakesp/", env!("CARGO_PKG_VERSION"));
 
-/// The hostname of the API provider for getting the public IP address.
-const PUBLIC_IP_PROVIDER_HOST: &str = "api.ipify.org";
-/// The HTTP request format for getting the public IP address.
-const PUBLIC_IP_PROVIDER_REQUEST: &[u8] =
-    b"GET / HTTP/1.1\r\nHost: api.ipify.org\r\nConnection: close\r\n\r\n";
+/// The buffer size for the update requests.
+const REQUEST_BUFFER_SIZE: usize = 1024;
+/// The buffer size for the responses.
+/// It should be big enough to contain the headers and the body of the responses of the providers.
+const RESPONSE_BUFFER_SIZE: usize = 2048;
 
-/// The buffer size for the TCP socket.
-/// It should be big enough to contain the HTTP requests and responses.
-const TCP_BUFFER_SIZE: usize = 1024;
+/// The publication of the record of an address family.
+#[derive(Debug, Clone, Copy)]
+struct Record {
+    /// When the address was published, or the boot time for an address saved in flash.
+    published_at: Instant,
+    /// When to check that the hostname resolves to the published address, if it was not checked yet.
+    verify_at: Option<Instant>,
+    /// Whether the update was already sent again because the hostname resolved to another address.
+    resent: bool,
+}
+
+impl Record {
+    /// Get the index of the record of the family of the address.
+    fn index(ip: &IpAddress) -> usize {
+        match ip {
+            IpAddress::Ipv4(_) => 0,
+            IpAddress::Ipv6(_) => 1,
+        }
+    }
+}
+
+/// Check if the IPv6 record is updated, which needs the device to configure its IPv6 address.
+pub const fn ipv6_enabled() -> bool {
+    matches!(DNS_IPV6_ENABLE.as_bytes(), b"true" | b"1")
+}
+
+/// Check if the requests to the DNS provider are sent over HTTPS, which needs the `tls` feature.
+const fn tls_enabled() -> bool {
+    #[cfg(feature = "tls")]
+    return matches!(DNS_TLS_ENABLE.as_bytes(), b"true" | b"1");
+    #[cfg(not(feature = "tls"))]
+    false
+}
 
 /// The embassy task that handles the DNS updater.
 #[embassy_executor::task]
-pub async fn dns_updater_task(stack: Stack<'static>) {
+pub async fn dns_updater_task(stack: Stack<'static>, rng: Rng) {
     let delay_seconds = get_dns_check_delay(DNS_CHECK_DELAY);
-    let mut prev_public_ip = None;
+    let refresh_interval = get_dns_refresh_interval(DNS_REFRESH_INTERVAL);
+    let verify_delay = Duration::from_secs(get_dns_verify_delay(DNS_VERIFY_DELAY));
+    let mut backoff = Backoff::new(rng);
+    // The time the saved addresses were published is unknown, so the refresh interval starts at boot
+    // and they are verified at the first check
+    let mut published = published::load();
+    let mut records = [Record {
+        published_at: Instant::now(),
+        verify_at: verify::is_enabled().then(Instant::now),
+        resent: false,
+    }; 2];
+    if published.stopped {
+        update_state(|state| {
+            state.dns_update = Some(DnsUpdate {
+                time: Instant::now(),
+                success: false,
+                message: "Stopped by a fatal error before the restart",
+                stopped: true,
+            })
+        });
+    }
     loop {
         wait_for_connection(stack).await;
 
         // Get the public IP address
-        let public_ip_response =
-            match send_http_request(stack, PUBLIC_IP_PROVIDER_HOST, PUBLIC_IP_PROVIDER_REQUEST)
-                .await
-            {
-                Ok(Some(v)) => {
-                    log::info!("DNS | Got response from {}:", PUBLIC_IP_PROVIDER_HOST);
-                    v
-                }
-                Ok(None) => {
-                    log::error!("DNS | Got empty response from public IP provider");
-                    continue;
+        // A failed lookup of one family does not prevent the record of the other one from being updated
+        let (public_ip, retry_after) = match get_public_ip(stack, rng).await {
+            Ok(v) => {
+                log::info!("DNS | Public IP address: {}", v);
+                if get_state().public_ip != Some(v) {
+                    publish_event(Event::PublicIpChanged { ip: v });
                 }
-                Err(_) => continue,
-            };
-
-        // Remove the HTTP headers
-        let public_ip_str = match public_ip_response.split("\r\n\r\n").last() {
-            Some(v) => v,
-            None => {
-                log::error!("DNS | Public IP address not found in response");
-                log::error!("{}", public_ip_response);
-                continue;
+                update_state(|state| state.public_ip = Some(v));
+                (Some(v), None)
             }
+            Err(retry_after) => (None, retry_after),
         };
 
-        // Parse the public IP address
-        let public_ip = match parse_ip_address(public_ip_str) {
-            Ok(v) => {
-                log::info!("DNS | Public IP address: {}", v);
-                v
-            }
-            Err(e) => {
-                log::error!("DNS | Public IP address not found in response -> {}", e);
-                log::error!("{}", public_ip_response);
-                continue;
-            }
+        // The IPv6 address is only known once the device has a global address
+        let public_ipv6 = match ipv6_enabled() {
+            true => get_public_ipv6(stack).ok(),
+            false => None,
         };
+        if let Some(v) = public_ipv6 {
+            log::info!("DNS | Public IPv6 address: {}", v);
+            if get_state().public_ipv6 != Some(v) {
+                publish_event(Event::PublicIpChanged {
+                    ip: IpAddress::Ipv6(v),
+                });
+            }
+        }
+        update_state(|state| state.public_ipv6 = public_ipv6);
 
-        // Check if the public IP address has changed
-        // We only update the DNS if the IP address has changed
-        if Some(public_ip) == prev_public_ip {
+        if public_ip.is_none() && public_ipv6.is_none() {
+            backoff.wait(retry_after).await;
+            continue;
+        }
+
+        // Check if the public IP addresses have changed
+        // We only update the records of the addresses that have changed, or that are due for a refresh
+        let mut changed = Vec::<IpAddress, 2>::new();
+        for ip in [public_ip, public_ipv6.map(IpAddress::Ipv6)]
+            .into_iter()
+            .flatten()
+        {
+            let record = &mut records[Record::index(&ip)];
+            if published.get(&ip) != Some(ip) {
+                record.resent = false;
+                let _ = changed.push(ip);
+            } else if refresh_interval.is_some_and(|v| record.published_at.elapsed() >= v) {
+                log::info!("DNS | Refreshing the record of {}", ip);
+                let _ = changed.push(ip);
+            } else if record.verify_at.is_some_and(|v| v <= Instant::now())
+                && !verify_record(stack, ip, record).await
+            {
+                let _ = changed.push(ip);
+            }
+        }
+        if changed.is_empty() {
+            backoff.reset();
             log::info!(
                 "DNS | Public IP address has not changed. Next check in {} seconds",
                 delay_seconds
@@ -82,26 +213,160 @@ pub async fn dns_updater_task(stack: Stack<'static>) {
             continue;
         }
 
-        // Update the DNS
-        match send_http_request(stack, DNS_HOST, DNS_HTTP_REQUEST).await {
-            Ok(Some(v)) => {
-                log::info!("DNS | Got response from {}:", DNS_HOST);
-                let (_, tail) = v.split_once("\r\n\r\n").unwrap_or((v.as_str(), ""));
-                if tail.is_empty() {
-                    log::warn!("DNS | Response was empty");
-                } else {
-                    log::info!("...\r\n{}", tail);
-                }
+        // Retrying after a fatal error would only get the account blocked, even after a restart
+        if published.stopped {
+            log::warn!(
+                "DNS | Updates are stopped after a fatal error. Fix the configuration and flash the device again"
+            );
+            Timer::after(Duration::from_secs(delay_seconds)).await;
+            continue;
+        }
+
+        // Update the records one after the other, stopping at the first failure
+        let values = Values {
+            ip: public_ip,
+            ipv6: public_ipv6,
+            hostname: get_state().hostname,
+        };
+        let mut failure = None;
+        for ip in changed {
+            if let Err(e) = update_record(stack, ip, &values).await {
+                published.stopped = matches!(e, (UpdateError::Fatal(_), _));
+                failure = Some(e);
+                break;
+            }
+            // Refreshes and updates sent again publish the same address
+            if published.get(&ip) != Some(ip) {
+                update_metrics(|metrics| metrics.dns_last_change = Some(Instant::now()));
+            }
+            published.set(ip);
+            let record = &mut records[Record::index(&ip)];
+            record.published_at = Instant::now();
+            record.verify_at = verify::is_enabled().then(|| Instant::now() + verify_delay);
+        }
+        published::save(&published);
+        match failure {
+            None => {
                 log::info!("DNS | DNS updated. Next check in {} seconds", delay_seconds);
-                prev_public_ip = Some(public_ip);
+                backoff.reset();
+                Timer::after(Duration::from_secs(delay_seconds)).await;
+            }
+            Some((UpdateError::Temporary(e), retry_after)) => {
+                log::error!("DNS | Error updating DNS: {}", e);
+                backoff.wait(retry_after).await;
+            }
+            Some((UpdateError::Fatal(e), _)) => {
+                log::error!("DNS | Error updating DNS: {}", e);
+                log::error!("DNS | Stopping the updates until the configuration changes");
                 Timer::after(Duration::from_secs(delay_seconds)).await;
             }
-            Ok(None) => log::warn!("DNS | Response was empty"),
-            Err(_) => log::error!("DNS | Error updating DNS"),
         };
     }
 }
 
+/// Check that the hostname resolves to the published address and save the result in the state.
+/// Returns `false` if the update should be sent again, which is only done once per address
+/// so that a wrong configuration does not send updates forever.
+async fn verify_record(stack: Stack<'_>, ip: IpAddress, record: &mut Record) -> bool {
+    let matches = match verify::resolves_to(stack, ip).await {
+        Ok(v) => v,
+        // Try again at the next check
+        Err(_) => return true,
+    };
+    record.verify_at = None;
+    update_state(|state| match ip {
+        IpAddress::Ipv4(_) => state.dns_verified = Some(matches),
+        IpAddress::Ipv6(_) => state.dns_verified_ipv6 = Some(matches),
+    });
+    if matches {
+        log::info!(
+            "DNS | Verified that {} resolves to {}",
+            verify::DNS_VERIFY_HOSTNAME,
+            ip
+        );
+        return true;
+    }
+
+    log::warn!(
+        "DNS | {} does not resolve to the published address {}",
+        verify::DNS_VERIFY_HOSTNAME,
+        ip
+    );
+    if record.resent {
+        log::warn!(
+            "DNS | The update was already sent again, check the configuration of the record"
+        );
+        return true;
+    }
+    log::warn!("DNS | Sending the update again");
+    record.resent = true;
+    false
+}
+
+/// Update the record of the address with the provider and save the result in the state.
+/// Returns the delay asked with a `Retry-After` header or by the provider on failure, if any.
+async fn update_record(
+    stack: Stack<'_>,
+    ip: IpAddress,
+    values: &Values<'_>,
+) -> Result<(), (UpdateError, Option<u64>)> {
+    // Fill the update request with the public IP address
+    let request = match PROVIDER.build_request(ip, values) {
+        Ok(v) => v,
+        Err(_) => {
+            log::error!(
+                "DNS | Could not build the update request for {:?} and {}",
+                PROVIDER,
+                ip
+            );
+            return Err((UpdateError::Temporary("Could not build the request"), None));
+        }
+    };
+
+    // Update the DNS
+    let dns_host = PROVIDER.host();
+    let mut response_buf = [0; RESPONSE_BUFFER_SIZE];
+    let response = send_http_request(stack, dns_host, request.as_bytes(), &mut response_buf).await;
+    let mut retry_after = None;
+    let result = match &response {
+        Ok(v) => {
+            retry_after = v.retry_after();
+            log::info!("DNS | Got response from {}: {}", dns_host, v.status);
+            match v.text() {
+                Some(body) => {
+                    // Server errors of the provider may ask for a longer delay than the header
+                    retry_after = retry_after.max(PROVIDER.retry_delay(body));
+                    if body.is_empty() {
+                        log::warn!("DNS | Response was empty");
+                    } else {
+                        log::info!("...\r\n{}", body);
+                    }
+                    PROVIDER.check_response(v.status, body)
+                }
+                None => Err(UpdateError::Temporary("Response was not UTF8")),
+            }
+        }
+        Err(_) => Err(UpdateError::Temporary("Could not send the request")),
+    };
+    let success = result.is_ok();
+    update_state(|state| {
+        state.dns_update = Some(DnsUpdate {
+            time: Instant::now(),
+            success,
+            message: result.err().map_or("Record updated", |e| e.message()),
+            stopped: matches!(result, Err(UpdateError::Fatal(_))),
+        })
+    });
+    update_metrics(|metrics| {
+        if success {
+            metrics.dns_update_successes += 1;
+        } else {
+            metrics.dns_update_failures += 1;
+        }
+    });
+    result.map_err(|e| (e, retry_after))
+}
+
 /// Parse the DNS check delay and fallback to the fallback delay if there is an error
 fn get_dns_check_delay(delay: &str) -> u64 {
     match delay.parse::<u64>() {
@@ -122,86 +387,92 @@ fn get_dns_check_delay(delay: &str) -> u64 {
     }
 }
 
-/// Sends an HTTP request to the target host and returns its response.
-async fn send_http_request(
-    stack: Stack<'_>,
-    target_host: &'static str,
-    request: &'static [u8],
-) -> Result<Option<String<TCP_BUFFER_SIZE>>, ()> {
-    // Get public IP address
-    let remote_endpoint = get_dns_address(stack, target_host).await?;
-
-    // Setup TCP socket
-    let mut rx_buffer = [0; TCP_BUFFER_SIZE];
-    let mut tx_buffer = [0; TCP_BUFFER_SIZE];
-    let mut socket = TcpSocket::new(stack, &mut rx_buffer, &mut tx_buffer);
-    socket.set_timeout(Some(embassy_time::Duration::from_secs(10)));
-
-    // Connect to the remote endpoint
-    log::info!("DNS | Connecting to {}...", target_host);
-    if let Err(e) = socket.connect(remote_endpoint).await {
-        abort_connection(&mut socket).await;
-        log::error!("DNS | Error connecting to {}: {:?}", target_host, e);
-        return Err(());
-    }
-    log::info!("DNS | Connected to {}!", target_host);
-
-    // Send the HTTP request to update the IP address
-    log::info!("DNS | Writing HTTP request to {}...", target_host);
-    if (write_tcp_buf(&mut socket, request).await).is_err() {
-        abort_connection(&mut socket).await;
-        log::error!("DNS | Error writing request to {}", target_host);
-        return Err(());
-    }
-
-    // Get response length
-    let mut response_buf = [0; TCP_BUFFER_SIZE];
-    let response_len = match socket.read(&mut response_buf).await {
-        Ok(n) => n,
+/// Parse the DNS verify delay and fallback to the fallback delay if there is an error
+fn get_dns_verify_delay(delay: &str) -> u64 {
+    match delay.parse::<u64>() {
+        Ok(v) => v,
         Err(e) => {
-            abort_connection(&mut socket).await;
-            log::error!("DNS | Error reading response from {}: {:?}", target_host, e);
-            return Err(());
-        }
-    };
+            log::error!(
+                "DNS | Error parsing DNS_VERIFY_DELAY to u64 -> {}: {}",
+                e,
+                delay
+            );
 
-    // Parse the response as UTF8
-    let response = match String::from_utf8(Vec::from_slice(&response_buf[..response_len]).unwrap())
-    {
-        Ok(v) => Ok(Some(v)),
-        Err(e) => {
-            log::warn!("DNS | Response was not UTF8: {:?}", e);
-            Ok(None)
+            log::error!(
+                "DNS | Using fallback DNS verify delay: {} seconds",
+                DNS_VERIFY_DELAY_FALLBACK
+            );
+            DNS_VERIFY_DELAY_FALLBACK
         }
-    };
-
-    socket.close();
-    Timer::after(Duration::from_millis(500)).await;
-    abort_connection(&mut socket).await;
-    response
+    }
 }
 
-/// Queries the DNS server for the IP address of the target host.
-async fn get_dns_address(stack: Stack<'_>, target_host: &'static str) -> Result<IpEndpoint, ()> {
-    // Resolve the IP of the remote endpoint
-    log::info!("DNS | Resolving IP for {}...", target_host);
-    let ip_list = match stack.dns_query(target_host, DnsQueryType::A).await {
+/// Parse the DNS refresh interval and fallback to the fallback interval if there is an error.
+/// Returns `None` if the records are only updated when the address changes.
+fn get_dns_refresh_interval(interval: &str) -> Option<Duration> {
+    let seconds = match interval.parse::<u64>() {
         Ok(v) => v,
         Err(e) => {
-            log::error!("DNS | Error querying DNS server: {:?}", e);
-            return Err(());
+            log::error!(
+                "DNS | Error parsing DNS_REFRESH_INTERVAL to u64 -> {}: {}",
+                e,
+                interval
+            );
+
+            log::error!(
+                "DNS | Using fallback DNS refresh interval: {} seconds",
+                DNS_REFRESH_INTERVAL_FALLBACK
+            );
+            DNS_REFRESH_INTERVAL_FALLBACK
         }
     };
+    (seconds > 0).then(|| Duration::from_secs(seconds))
+}
+
+/// Sends an HTTP request to the target host and reads its response into the buffer.
+/// The request is sent over HTTPS when `DNS_TLS_ENABLE` is set.
+async fn send_http_request<'b>(
+    stack: Stack<'_>,
+    target_host: &str,
+    request: &[u8],
+    response_buf: &'b mut [u8],
+) -> Result<Response<'b>, ()> {
+    #[cfg(feature = "tls")]
+    let (port, transport) = match tls_enabled() {
+        true => (HTTPS_PORT, Transport::Tls(get_verification(target_host)?)),
+        false => (HTTP_PORT, Transport::Plain),
+    };
+    #[cfg(not(feature = "tls"))]
+    let (port, transport) = (HTTP_PORT, Transport::Plain);
 
-    // Get the first IPv4 address in the list
-    let remote_endpoint;
-    if let Some(ipv4_addr) = ip_list.iter().find(|x| matches!(x, IpAddress::Ipv4(_))) {
-        remote_endpoint = IpEndpoint::new(*ipv4_addr, 80);
-        log::info!("DNS | Found IP for {}: {}", target_host, ipv4_addr);
-    } else {
-        log::error!("DNS | No IP found for {}", target_host);
-        return Err(());
+    send_request(
+        stack,
+        target_host,
+        port,
+        &transport,
+        request,
+        response_buf,
+        "DNS",
+    )
+    .await
+}
+
+/// Get how the certificate of the target host is verified.
+/// The pinned fingerprint only applies to the update service of the DNS provider.
+#[cfg(feature = "tls")]
+fn get_verification(target_host: &str) -> Result<Verification<'static>, ()> {
+    if target_host != PROVIDER.host() || DNS_TLS_PINNED_FINGERPRINT.is_empty() {
+        return Ok(Verification::CaChain(CA_CERTIFICATES));
     }
 
-    Ok(remote_endpoint)
+    match parse_fingerprint(DNS_TLS_PINNED_FINGERPRINT) {
+        Ok(v) => Ok(Verification::Fingerprint(v)),
+        Err(_) => {
+            log::error!(
+                "DNS | Error parsing DNS_TLS_PINNED_FINGERPRINT: {}",
+                DNS_TLS_PINNED_FINGERPRINT
+            );
+            Err(())
+        }
+    }
 }
@@ -0,0 +1,47 @@
+use embassy_time::{Duration, Timer};
+use esp_hal::rng::Rng;
+
+/// The maximum delay before the first retry, in seconds.
+const BACKOFF_BASE: u64 = 10;
+/// The maximum delay between retries, in seconds.
+const BACKOFF_MAX: u64 = 1800;
+/// The maximum delay asked by a `Retry-After` header that is honoured, in seconds.
+const RETRY_AFTER_MAX: u64 = 86400;
+
+/// An exponential backoff with jitter between the retries of failed requests.
+pub struct Backoff {
+    rng: Rng,
+    /// The number of failures since the last success.
+    failures: u32,
+}
+
+impl Backoff {
+    pub fn new(rng: Rng) -> Self {
+        Self { rng, failures: 0 }
+    }
+
+    /// Forget the previous failures after a success.
+    pub fn reset(&mut self) {
+        self.failures = 0;
+    }
+
+    /// Get the delay in seconds before the next retry, which doubles after each failure up to a maximum.
+    /// Half of the delay is random so that devices failing at the same time do not retry together.
+    /// A longer delay asked by the server with a `Retry-After` header is honoured.
+    pub fn next_delay(&mut self, retry_after: Option<u64>) -> u64 {
+        let max = (BACKOFF_BASE << self.failures.min(16)).min(BACKOFF_MAX);
+        self.failures = self.failures.saturating_add(1);
+        let delay = max / 2 + self.rng.random() as u64 % (max / 2 + 1);
+        match retry_after {
+            Some(v) => delay.max(v.min(RETRY_AFTER_MAX)),
+            None => delay,
+        }
+    }
+
+    /// Wait before retrying after a failure.
+    pub async fn wait(&mut self, retry_after: Option<u64>) {
+        let delay = self.next_delay(retry_after);
+        log::info!("DNS | Retrying in {} seconds", delay);
+        Timer::after(Duration::from_secs(delay)).await;
+    }
+}
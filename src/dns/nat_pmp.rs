@@ -0,0 +1,150 @@
+mod message;
+
+use core::net::Ipv4Addr;
+use embassy_net::{
+    IpAddress, IpEndpoint, Stack,
+    udp::{PacketMetadata, UdpSocket},
+};
+use embassy_time::{Duration, with_timeout};
+use esp_hal::rng::Rng;
+use message::{EXTERNAL_ADDRESS_REQUEST, Reply, map_request, parse_map_response, parse_response};
+
+/// The port of the NAT-PMP and PCP server of the gateway.
+const NAT_PMP_PORT: u16 = 5351;
+/// The buffer size for the NAT-PMP and PCP messages.
+const UDP_BUFFER_SIZE: usize = 128;
+/// The number of times the request is sent when no response is received.
+const MAX_TRIES: u32 = 4;
+/// The time in milliseconds to wait for the first response, which doubles after each try.
+const RESPONSE_TIMEOUT: u64 = 250;
+/// The lifetime in seconds asked for the mapping, which is deleted once the address is known.
+const PCP_MAP_LIFETIME: u32 = 120;
+
+/// Get the external address of the gateway with NAT-PMP (RFC 6886),
+/// or with a PCP MAP request (RFC 6887) if the gateway only supports PCP.
+pub async fn query_gateway(stack: Stack<'_>, gateway: Ipv4Addr, rng: Rng) -> Result<IpAddress, ()> {
+    let server = IpEndpoint::new(IpAddress::Ipv4(gateway), NAT_PMP_PORT);
+
+    // Setup UDP socket
+    let mut rx_meta = [PacketMetadata::EMPTY; 2];
+    let mut rx_buffer = [0; UDP_BUFFER_SIZE];
+    let mut tx_meta = [PacketMetadata::EMPTY; 2];
+    let mut tx_buffer = [0; UDP_BUFFER_SIZE];
+    let mut socket = UdpSocket::new(
+        stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+    // Bind to a random local port
+    if let Err(e) = socket.bind(0) {
+        log::error!("DNS | Error binding UDP socket: {:?}", e);
+        return Err(());
+    }
+
+    log::info!("DNS | Sending NAT-PMP request to {}...", gateway);
+    let mut result = exchange(
+        &mut socket,
+        server,
+        &EXTERNAL_ADDRESS_REQUEST,
+        parse_response,
+    )
+    .await;
+    if let Some(Reply::PcpOnly) = result {
+        log::info!(
+            "DNS | Gateway {} only supports PCP, sending a PCP request...",
+            gateway
+        );
+        result = match stack.config_v4() {
+            Some(config) => query_pcp(&mut socket, server, config.address.address(), rng).await,
+            None => {
+                log::error!("DNS | Device has no IPv4 address");
+                Some(Reply::Error(0))
+            }
+        };
+    }
+    socket.close();
+
+    match result {
+        Some(Reply::Address(v)) => Ok(IpAddress::Ipv4(v)),
+        Some(Reply::Ipv6Address) => {
+            log::error!(
+                "DNS | PCP gateway {} returned an IPv6 external address",
+                gateway
+            );
+            Err(())
+        }
+        Some(Reply::Error(code)) => {
+            log::error!("DNS | Gateway {} returned an error: {}", gateway, code);
+            Err(())
+        }
+        Some(Reply::PcpOnly) | None => {
+            log::error!("DNS | No NAT-PMP or PCP response from gateway {}", gateway);
+            Err(())
+        }
+    }
+}
+
+/// Ask the gateway for a short mapping of the port of the socket with PCP to learn its external address,
+/// then delete the mapping.
+async fn query_pcp(
+    socket: &mut UdpSocket<'_>,
+    server: IpEndpoint,
+    client: Ipv4Addr,
+    mut rng: Rng,
+) -> Option<Reply> {
+    // The random nonce prevents other hosts from forging the response
+    let mut nonce = [0u8; 12];
+    rng.read(&mut nonce);
+    let port = socket.endpoint().port;
+
+    let request = map_request(client, port, &nonce, PCP_MAP_LIFETIME);
+    let result = exchange(socket, server, &request, |v| parse_map_response(v, &nonce)).await;
+
+    // A mapping with a lifetime of 0 is deleted, and the response is not needed
+    if matches!(result, Some(Reply::Address(_) | Reply::Ipv6Address)) {
+        let request = map_request(client, port, &nonce, 0);
+        if let Err(e) = socket.send_to(&request, server).await {
+            log::warn!("DNS | Error deleting PCP mapping: {:?}", e);
+        }
+    }
+    result
+}
+
+/// Send the request to the gateway until a response is received.
+/// UDP is unreliable, so the request is sent again after a timeout that doubles after each try.
+/// Returns `None` if no response was received.
+async fn exchange(
+    socket: &mut UdpSocket<'_>,
+    server: IpEndpoint,
+    request: &[u8],
+    mut parse: impl FnMut(&[u8]) -> Option<Reply>,
+) -> Option<Reply> {
+    let mut buf = [0u8; UDP_BUFFER_SIZE];
+    for i in 0..MAX_TRIES {
+        if let Err(e) = socket.send_to(request, server).await {
+            log::error!("DNS | Error sending request to {}: {:?}", server, e);
+            return None;
+        }
+
+        let timeout = Duration::from_millis(RESPONSE_TIMEOUT << i);
+        let response = with_timeout(timeout, async {
+            loop {
+                match socket.recv_from(&mut buf).await {
+                    Ok((len, meta)) if meta.endpoint == server => {
+                        if let Some(v) = parse(&buf[..len]) {
+                            break v;
+                        }
+                    }
+                    _ => continue,
+                }
+            }
+        })
+        .await;
+        if let Ok(v) = response {
+            return Some(v);
+        }
+    }
+    None
+}
@@ -0,0 +1,207 @@
+use core::net::{Ipv4Addr, Ipv6Addr};
+
+/// The request for the external address: version 0 and opcode 0.
+pub const EXTERNAL_ADDRESS_REQUEST: [u8; 2] = [0, 0];
+/// The opcode of the response to the request for the external address.
+const EXTERNAL_ADDRESS_RESPONSE: u8 = 128;
+
+/// The version of PCP, which replaced NAT-PMP and answers the requests of unsupported versions with its own.
+const PCP_VERSION: u8 = 2;
+/// The opcode of the PCP requests creating a mapping, whose response gives the external address.
+const PCP_OPCODE_MAP: u8 = 1;
+/// The bit set in the opcode of the PCP responses.
+const PCP_RESPONSE_BIT: u8 = 0x80;
+/// The size of the PCP MAP requests and responses: the common header and the MAP data.
+const PCP_MAP_SIZE: usize = 60;
+/// The protocol of the mapping, which is UDP.
+const PCP_PROTOCOL_UDP: u8 = 17;
+
+/// A response of the gateway.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reply {
+    /// The external address of the gateway.
+    Address(Ipv4Addr),
+    /// The gateway returned an IPv6 external address to the PCP request, which is not the public IPv4 address.
+    Ipv6Address,
+    /// The result code of an error.
+    Error(u16),
+    /// The gateway only supports PCP and refused the NAT-PMP request.
+    PcpOnly,
+}
+
+/// Parse the response to the NAT-PMP request for the external address.
+/// Returns `None` if the message is not the response.
+pub fn parse_response(message: &[u8]) -> Option<Reply> {
+    // PCP servers without NAT-PMP support answer with an unsupported version error
+    if message.first() == Some(&PCP_VERSION) {
+        return Some(Reply::PcpOnly);
+    }
+    if message.len() < 12 || message[0] != 0 || message[1] != EXTERNAL_ADDRESS_RESPONSE {
+        return None;
+    }
+    match u16::from_be_bytes([message[2], message[3]]) {
+        0 => Some(Reply::Address(Ipv4Addr::new(
+            message[8],
+            message[9],
+            message[10],
+            message[11],
+        ))),
+        code => Some(Reply::Error(code)),
+    }
+}
+
+/// Build a PCP MAP request for the UDP port of the client, letting the gateway choose the external port.
+pub fn map_request(
+    client: Ipv4Addr,
+    port: u16,
+    nonce: &[u8; 12],
+    lifetime: u32,
+) -> [u8; PCP_MAP_SIZE] {
+    let mut request = [0u8; PCP_MAP_SIZE];
+    request[0] = PCP_VERSION;
+    request[1] = PCP_OPCODE_MAP;
+    request[4..8].copy_from_slice(&lifetime.to_be_bytes());
+    // The IPv4 addresses are written as IPv4-mapped IPv6 addresses
+    request[8..24].copy_from_slice(&client.to_ipv6_mapped().octets());
+    request[24..36].copy_from_slice(nonce);
+    request[36] = PCP_PROTOCOL_UDP;
+    request[40..42].copy_from_slice(&port.to_be_bytes());
+    // No external address is suggested, which is the IPv4-mapped unspecified address for IPv4
+    request[44..60].copy_from_slice(&Ipv4Addr::UNSPECIFIED.to_ipv6_mapped().octets());
+    request
+}
+
+/// Parse the response to the PCP MAP request with the given nonce.
+/// Returns `None` if the message is not the response.
+pub fn parse_map_response(message: &[u8], nonce: &[u8; 12]) -> Option<Reply> {
+    if message.len() < PCP_MAP_SIZE
+        || message[0] != PCP_VERSION
+        || message[1] != PCP_RESPONSE_BIT | PCP_OPCODE_MAP
+        || message[24..36] != *nonce
+    {
+        return None;
+    }
+    match message[3] {
+        0 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&message[44..60]);
+            match Ipv6Addr::from(octets).to_ipv4_mapped() {
+                Some(v) => Some(Reply::Address(v)),
+                None => Some(Reply::Ipv6Address),
+            }
+        }
+        code => Some(Reply::Error(code as u16)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NONCE: [u8; 12] = *b"wakesp-nonce";
+    const CLIENT: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 20);
+    const EXTERNAL: Ipv4Addr = Ipv4Addr::new(203, 0, 113, 5);
+
+    /// Build the NAT-PMP response to the request for the external address with the given result code.
+    fn response(code: u16) -> [u8; 12] {
+        let mut message = [0u8; 12];
+        message[1] = EXTERNAL_ADDRESS_RESPONSE;
+        message[2..4].copy_from_slice(&code.to_be_bytes());
+        // The seconds since the mappings of the gateway were reset
+        message[4..8].copy_from_slice(&3600u32.to_be_bytes());
+        message[8..12].copy_from_slice(&EXTERNAL.octets());
+        message
+    }
+
+    /// Build the PCP response to the MAP request with the given result code and external address.
+    fn map_response(code: u8, nonce: &[u8; 12], external: Ipv6Addr) -> [u8; PCP_MAP_SIZE] {
+        let mut message = map_request(CLIENT, 40000, nonce, 120);
+        message[1] |= PCP_RESPONSE_BIT;
+        message[3] = code;
+        message[8..24].fill(0);
+        message[42..44].copy_from_slice(&40000u16.to_be_bytes());
+        message[44..60].copy_from_slice(&external.octets());
+        message
+    }
+
+    #[test]
+    fn parses_external_address() {
+        assert_eq!(parse_response(&response(0)), Some(Reply::Address(EXTERNAL)));
+    }
+
+    #[test]
+    fn parses_error() {
+        // 3: network failure, when the gateway has no external address yet
+        assert_eq!(parse_response(&response(3)), Some(Reply::Error(3)));
+    }
+
+    #[test]
+    fn detects_pcp_only_gateway() {
+        // A PCP server answers the request of version 0 with its version and an unsupported version error
+        let mut message = [0u8; 24];
+        message[0] = PCP_VERSION;
+        message[1] = PCP_RESPONSE_BIT;
+        message[3] = 1;
+        assert_eq!(parse_response(&message), Some(Reply::PcpOnly));
+    }
+
+    #[test]
+    fn ignores_other_messages() {
+        assert_eq!(parse_response(&EXTERNAL_ADDRESS_REQUEST), None);
+        assert_eq!(parse_response(&response(0)[..11]), None);
+        assert_eq!(parse_response(&[]), None);
+    }
+
+    #[test]
+    fn writes_map_request() {
+        let request = map_request(CLIENT, 40000, &NONCE, 120);
+        assert_eq!(request[..8], [2, 1, 0, 0, 0, 0, 0, 120]);
+        assert_eq!(request[8..24], CLIENT.to_ipv6_mapped().octets());
+        assert_eq!(request[24..36], NONCE);
+        assert_eq!(request[36..40], [PCP_PROTOCOL_UDP, 0, 0, 0]);
+        assert_eq!(request[40..44], [0x9c, 0x40, 0, 0]);
+        assert_eq!(
+            request[44..60],
+            Ipv4Addr::UNSPECIFIED.to_ipv6_mapped().octets()
+        );
+    }
+
+    #[test]
+    fn parses_map_response() {
+        let message = map_response(0, &NONCE, EXTERNAL.to_ipv6_mapped());
+        assert_eq!(
+            parse_map_response(&message, &NONCE),
+            Some(Reply::Address(EXTERNAL))
+        );
+        // 2: not authorized
+        let message = map_response(2, &NONCE, EXTERNAL.to_ipv6_mapped());
+        assert_eq!(parse_map_response(&message, &NONCE), Some(Reply::Error(2)));
+    }
+
+    #[test]
+    fn rejects_other_nonce() {
+        let message = map_response(0, b"other--nonce", EXTERNAL.to_ipv6_mapped());
+        assert_eq!(parse_map_response(&message, &NONCE), None);
+    }
+
+    #[test]
+    fn detects_ipv6_external_address() {
+        let message = map_response(0, &NONCE, "2001:db8::5".parse().unwrap());
+        assert_eq!(
+            parse_map_response(&message, &NONCE),
+            Some(Reply::Ipv6Address)
+        );
+    }
+
+    #[test]
+    fn ignores_other_map_messages() {
+        let message = map_response(0, &NONCE, EXTERNAL.to_ipv6_mapped());
+        assert_eq!(
+            parse_map_response(&message[..PCP_MAP_SIZE - 1], &NONCE),
+            None
+        );
+        // The request itself
+        let request = map_request(CLIENT, 40000, &NONCE, 120);
+        assert_eq!(parse_map_response(&request, &NONCE), None);
+    }
+}
@@ -0,0 +1,367 @@
+use super::{
+    DNS_HOST, DNS_HTTP_REQUEST, DNS_HTTP_REQUEST_IPV6, REQUEST_BUFFER_SIZE, USER_AGENT,
+    ipv6_enabled,
+    template::{self, Values},
+    tls_enabled,
+};
+use crate::utils::encode_base64;
+use core::fmt::Write;
+use embassy_net::IpAddress;
+use heapless::String;
+
+/// The dynamic DNS provider, one of "custom", "dyndns2", "duckdns", "namecheap", "cloudflare" or "desec".
+const DNS_PROVIDER: &str = env!("DNS_PROVIDER");
+/// The record to update: a hostname, a subdomain for DuckDNS, a host for Namecheap or a record ID for Cloudflare.
+const DNS_RECORD: &str = env!("DNS_RECORD");
+/// The record ID of the IPv6 record for Cloudflare, which has one ID per record.
+const DNS_RECORD_IPV6: &str = env!("DNS_RECORD_IPV6");
+/// The zone of the record: a domain for Namecheap or a zone ID for Cloudflare.
+const DNS_ZONE: &str = env!("DNS_ZONE");
+/// The username of the dyndns2 protocol.
+const DNS_USERNAME: &str = env!("DNS_USERNAME");
+/// The password or token used to authenticate with the provider.
+const DNS_PASSWORD: &str = env!("DNS_PASSWORD");
+
+/// The provider selected at build time.
+pub const PROVIDER: Provider = Provider::from_name(DNS_PROVIDER);
+const _: () = PROVIDER.validate();
+
+/// The maximum size of the `username:password` credentials of the dyndns2 protocol.
+const CREDENTIALS_SIZE: usize = 128;
+/// The reason of the failure when the response of the provider is not understood.
+const UNEXPECTED_RESPONSE: &str = "Unexpected response";
+/// The minimum delay in seconds before retrying after a server error of the dyndns2 protocol,
+/// which asks the clients to wait at least 30 minutes.
+const DYNDNS2_RETRY_DELAY: u64 = 1800;
+/// The return codes of the dyndns2 protocol.
+const DYNDNS2_CODES: [&str; 11] = [
+    "good", "nochg", "badauth", "nohost", "notfqdn", "numhost", "badagent", "abuse", "!donator",
+    "dnserr", "911",
+];
+/// The size of the base64 encoded credentials.
+const ENCODED_CREDENTIALS_SIZE: usize = CREDENTIALS_SIZE.div_ceil(3) * 4;
+
+/// A dynamic DNS provider, which knows how to build the update request and read its response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Provider {
+    /// The request is taken from `DNS_HTTP_REQUEST` and sent to `DNS_HOST`.
+    Custom,
+    /// The dyndns2 protocol used by No-IP, Dyn and many others, on `DNS_HOST`.
+    Dyndns2,
+    DuckDns,
+    Namecheap,
+    /// The API v4 of Cloudflare, with an API token.
+    Cloudflare,
+    Desec,
+}
+
+impl Provider {
+    /// Get the provider from its name, failing the build if it is unknown.
+    const fn from_name(name: &str) -> Self {
+        match name.as_bytes() {
+            b"" | b"custom" => Provider::Custom,
+            b"dyndns2" => Provider::Dyndns2,
+            b"duckdns" => Provider::DuckDns,
+            b"namecheap" => Provider::Namecheap,
+            b"cloudflare" => Provider::Cloudflare,
+            b"desec" => Provider::Desec,
+            _ => panic!(
+                "Unknown DNS_PROVIDER, expected custom, dyndns2, duckdns, namecheap, cloudflare or desec"
+            ),
+        }
+    }
+
+    /// Check at compile time that the configuration needed by the provider is set.
+    const fn validate(self) {
+        match self {
+            Provider::Custom => template::validate(DNS_HTTP_REQUEST, REQUEST_BUFFER_SIZE),
+            Provider::Dyndns2 => {
+                if DNS_HOST.is_empty() || DNS_USERNAME.is_empty() {
+                    panic!("The dyndns2 provider needs DNS_HOST and DNS_USERNAME")
+                }
+                if DNS_USERNAME.len() + 1 + DNS_PASSWORD.len() > CREDENTIALS_SIZE {
+                    panic!("DNS_USERNAME and DNS_PASSWORD are too long")
+                }
+            }
+            Provider::Namecheap | Provider::Cloudflare => {
+                if DNS_ZONE.is_empty() {
+                    panic!("The namecheap and cloudflare providers need DNS_ZONE")
+                }
+            }
+            Provider::DuckDns | Provider::Desec => (),
+        }
+        // These providers only accept HTTPS, and the token must not be sent in clear text to port 80
+        if matches!(
+            self,
+            Provider::DuckDns | Provider::Cloudflare | Provider::Desec
+        ) && !tls_enabled()
+        {
+            panic!(
+                "The duckdns, cloudflare and desec providers need the tls feature and DNS_TLS_ENABLE"
+            )
+        }
+        if !matches!(self, Provider::Custom) && (DNS_RECORD.is_empty() || DNS_PASSWORD.is_empty()) {
+            panic!("DNS providers other than custom need DNS_RECORD and DNS_PASSWORD")
+        }
+
+        if !ipv6_enabled() {
+            return;
+        }
+        match self {
+            Provider::Custom => {
+                if DNS_HTTP_REQUEST_IPV6.is_empty() {
+                    panic!(
+                        "The custom provider needs DNS_HTTP_REQUEST_IPV6 to update the IPv6 record"
+                    )
+                }
+                template::validate(DNS_HTTP_REQUEST_IPV6, REQUEST_BUFFER_SIZE)
+            }
+            Provider::Namecheap => panic!("The namecheap provider does not support IPv6 records"),
+            Provider::Cloudflare => {
+                if DNS_RECORD_IPV6.is_empty() {
+                    panic!(
+                        "The cloudflare provider needs DNS_RECORD_IPV6 to update the IPv6 record"
+                    )
+                }
+            }
+            Provider::Dyndns2 | Provider::DuckDns | Provider::Desec => (),
+        }
+    }
+
+    /// The hostname of the update service of the provider.
+    pub fn host(self) -> &'static str {
+        match self {
+            Provider::Custom | Provider::Dyndns2 => DNS_HOST,
+            Provider::DuckDns => "www.duckdns.org",
+            Provider::Namecheap => "dynamicdns.park-your-domain.com",
+            Provider::Cloudflare => "api.cloudflare.com",
+            Provider::Desec => "update.dedyn.io",
+        }
+    }
+
+    /// Get the configuration identifying the record updated with the addresses of a family.
+    pub fn record_id(self, ipv6: bool) -> [&'static str; 4] {
+        let record = match (self, ipv6) {
+            (Provider::Custom, false) => DNS_HTTP_REQUEST,
+            (Provider::Custom, true) => DNS_HTTP_REQUEST_IPV6,
+            (Provider::Cloudflare, true) => DNS_RECORD_IPV6,
+            _ => DNS_RECORD,
+        };
+        [DNS_PROVIDER, self.host(), DNS_ZONE, record]
+    }
+
+    /// Get the credentials used to authenticate with the provider.
+    pub fn credentials(self) -> [&'static str; 2] {
+        [DNS_USERNAME, DNS_PASSWORD]
+    }
+
+    /// Build the HTTP request updating the record of the family of the IP address.
+    /// The values fill the placeholders of the requests of the custom provider.
+    pub fn build_request(
+        self,
+        ip: IpAddress,
+        values: &Values<'_>,
+    ) -> Result<String<REQUEST_BUFFER_SIZE>, ()> {
+        let mut request = String::new();
+        let mut body = String::<64>::new();
+        let written = match (self, ip) {
+            (Provider::Custom, IpAddress::Ipv4(_)) => {
+                return template::render(DNS_HTTP_REQUEST, values);
+            }
+            (Provider::Custom, IpAddress::Ipv6(_)) => {
+                return template::render(DNS_HTTP_REQUEST_IPV6, values);
+            }
+            // The dyndns2 protocol updates the record matching the family of the address
+            (Provider::Dyndns2, ip) => {
+                let mut credentials = String::<CREDENTIALS_SIZE>::new();
+                write!(credentials, "{}:{}", DNS_USERNAME, DNS_PASSWORD).map_err(|_| ())?;
+                let mut encoded = [0u8; ENCODED_CREDENTIALS_SIZE];
+                let len = encode_base64(credentials.as_bytes(), &mut encoded)?;
+                let encoded = core::str::from_utf8(&encoded[..len]).map_err(|_| ())?;
+                write!(
+                    request,
+                    "GET /nic/update?hostname={}&myip={} HTTP/1.1\r\nAuthorization: Basic {}\r\n",
+                    DNS_RECORD, ip, encoded
+                )
+            }
+            (Provider::DuckDns, IpAddress::Ipv4(ip)) => write!(
+                request,
+                "GET /update?domains={}&token={}&ip={} HTTP/1.1\r\n",
+                DNS_RECORD, DNS_PASSWORD, ip
+            ),
+            (Provider::DuckDns, IpAddress::Ipv6(ip)) => write!(
+                request,
+                "GET /update?domains={}&token={}&ipv6={} HTTP/1.1\r\n",
+                DNS_RECORD, DNS_PASSWORD, ip
+            ),
+            (Provider::Namecheap, IpAddress::Ipv4(ip)) => write!(
+                request,
+                "GET /update?host={}&domain={}&password={}&ip={} HTTP/1.1\r\n",
+                DNS_RECORD, DNS_ZONE, DNS_PASSWORD, ip
+            ),
+            (Provider::Namecheap, IpAddress::Ipv6(_)) => return Err(()),
+            (Provider::Cloudflare, ip) => write!(body, "{{\"content\":\"{}\"}}", ip)
+                .and_then(|_| {
+                    write!(
+                        request,
+                        "PATCH /client/v4/zones/{}/dns_records/{} HTTP/1.1\r\nAuthorization: Bearer {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n",
+                        DNS_ZONE,
+                        match ip {
+                            IpAddress::Ipv4(_) => DNS_RECORD,
+                            IpAddress::Ipv6(_) => DNS_RECORD_IPV6,
+                        },
+                        DNS_PASSWORD,
+                        body.len()
+                    )
+                }),
+            // The record of the other family is kept as is
+            (Provider::Desec, IpAddress::Ipv4(ip)) => write!(
+                request,
+                "GET /nic/update?hostname={}&myipv4={}&myipv6=preserve HTTP/1.1\r\nAuthorization: Token {}\r\n",
+                DNS_RECORD, ip, DNS_PASSWORD
+            ),
+            (Provider::Desec, IpAddress::Ipv6(ip)) => write!(
+                request,
+                "GET /nic/update?hostname={}&myipv4=preserve&myipv6={} HTTP/1.1\r\nAuthorization: Token {}\r\n",
+                DNS_RECORD, ip, DNS_PASSWORD
+            ),
+        };
+
+        written
+            .and_then(|_| write_common_headers(&mut request, self.host()))
+            .and_then(|_| request.write_str(&body))
+            .map_err(|_| ())?;
+        Ok(request)
+    }
+
+    /// Check the HTTP status and the body of the response of the provider.
+    /// Returns why the record was not updated on failure.
+    pub fn check_response(self, status: u16, body: &str) -> Result<(), UpdateError> {
+        match (status, self.check_body(body)) {
+            (200..=299, result) => result,
+            (_, Err(UpdateError::Fatal(e))) => Err(UpdateError::Fatal(e)),
+            (401 | 403, _) => Err(UpdateError::Fatal("Credentials were refused")),
+            (408 | 429, _) => Err(UpdateError::Temporary("Provider is busy")),
+            (400..=499, Err(UpdateError::Temporary(e))) if e != UNEXPECTED_RESPONSE => {
+                Err(UpdateError::Fatal(e))
+            }
+            (400..=499, _) => Err(UpdateError::Fatal("Request was refused")),
+            (500..=599, _) => Err(UpdateError::Temporary("Provider had an internal error")),
+            _ => Err(UpdateError::Temporary("Unexpected HTTP status")),
+        }
+    }
+
+    /// Get the minimum delay in seconds before retrying asked by the body of the response, if any.
+    pub fn retry_delay(self, body: &str) -> Option<u64> {
+        match self {
+            Provider::Dyndns2 | Provider::Desec => match dyndns2_code(body) {
+                Some("dnserr" | "911") => Some(DYNDNS2_RETRY_DELAY),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Check the return code of the provider in the body of the response.
+    fn check_body(self, body: &str) -> Result<(), UpdateError> {
+        match self {
+            // The format of the response of a custom request is unknown
+            Provider::Custom => Ok(()),
+            Provider::Dyndns2 | Provider::Desec => check_dyndns2_body(body),
+            Provider::DuckDns => match body.split_whitespace().find(|v| *v == "OK" || *v == "KO") {
+                Some("OK") => Ok(()),
+                Some(_) => Err(UpdateError::Fatal(
+                    "Update was refused, check the subdomain and the token",
+                )),
+                None => Err(UpdateError::Temporary(UNEXPECTED_RESPONSE)),
+            },
+            Provider::Namecheap => {
+                if body.contains("<ErrCount>0</ErrCount>") {
+                    return Ok(());
+                }
+                match body
+                    .split_once("<Err1>")
+                    .and_then(|(_, v)| v.split_once("</Err1>"))
+                {
+                    Some((error, _)) => {
+                        log::error!("DNS | Namecheap error: {}", error);
+                        Err(UpdateError::Fatal(
+                            "Update was refused, check the host, the domain and the password",
+                        ))
+                    }
+                    None => Err(UpdateError::Temporary(UNEXPECTED_RESPONSE)),
+                }
+            }
+            // The HTTP status tells if the error is fatal
+            Provider::Cloudflare => {
+                if body.contains("\"success\":true") {
+                    Ok(())
+                } else if body.contains("\"success\":false") {
+                    Err(UpdateError::Temporary(
+                        "Update was refused, check the zone, the record and the token",
+                    ))
+                } else {
+                    Err(UpdateError::Temporary(UNEXPECTED_RESPONSE))
+                }
+            }
+        }
+    }
+}
+
+/// Why a DNS update failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpdateError {
+    /// The update can be retried later.
+    Temporary(&'static str),
+    /// The configuration must be fixed before retrying, so the updates must stop.
+    Fatal(&'static str),
+}
+
+impl UpdateError {
+    /// The reason of the failure.
+    pub fn message(self) -> &'static str {
+        match self {
+            UpdateError::Temporary(v) | UpdateError::Fatal(v) => v,
+        }
+    }
+}
+
+/// Write the headers shared by the requests of all providers, ending the header section.
+fn write_common_headers(request: &mut impl Write, host: &str) -> core::fmt::Result {
+    write!(
+        request,
+        "Host: {}\r\nUser-Agent: {}\r\nConnection: close\r\n\r\n",
+        host, USER_AGENT
+    )
+}
+
+/// Check the return code of the dyndns2 protocol.
+/// The errors other than server errors must not be retried without a change of configuration.
+fn check_dyndns2_body(body: &str) -> Result<(), UpdateError> {
+    match dyndns2_code(body) {
+        Some("good" | "nochg") => Ok(()),
+        Some("badauth") => Err(UpdateError::Fatal("Invalid credentials")),
+        Some("nohost") => Err(UpdateError::Fatal(
+            "Hostname does not exist in this account",
+        )),
+        Some("notfqdn") => Err(UpdateError::Fatal(
+            "Hostname is not a fully qualified domain name",
+        )),
+        Some("numhost") => Err(UpdateError::Fatal("Too many hostnames in the update")),
+        Some("badagent") => Err(UpdateError::Fatal("User agent was blocked")),
+        Some("abuse") => Err(UpdateError::Fatal("Hostname was blocked for abuse")),
+        Some("!donator") => Err(UpdateError::Fatal(
+            "Feature is not available for this account",
+        )),
+        // Server errors are retried after `DYNDNS2_RETRY_DELAY`, see `Provider::retry_delay`
+        Some("dnserr" | "911") => Err(UpdateError::Temporary("Provider had an internal error")),
+        _ => Err(UpdateError::Temporary(UNEXPECTED_RESPONSE)),
+    }
+}
+
+/// Get the first return code of the dyndns2 protocol in the body of the response.
+/// The return code is followed by the IP address, and there is one line per hostname.
+fn dyndns2_code(body: &str) -> Option<&str> {
+    body.split_whitespace()
+        .find(|word| DYNDNS2_CODES.contains(word))
+}
@@ -0,0 +1,333 @@
+use super::{
+    RESPONSE_BUFFER_SIZE, USER_AGENT, nat_pmp, send_http_request,
+    stun::{self, STUN_PORT},
+    upnp,
+};
+use crate::utils::{get_global_ipv6, is_public_ip_address, parse_ip_address};
+use core::{
+    fmt::Write,
+    net::{Ipv4Addr, Ipv6Addr},
+};
+use embassy_net::{IpAddress, Stack};
+use esp_hal::rng::Rng;
+use heapless::{String, Vec};
+
+/// The comma separated list of the providers of the public IP address, queried in order.
+/// A provider is either a known name, a `host/path` URL returning the address as plain text,
+/// a `stun:host:port` STUN server, or the gateway with `natpmp` or `upnp`.
+const PUBLIC_IP_PROVIDERS: &str = env!("PUBLIC_IP_PROVIDERS");
+/// The fallback provider of the public IP address, used when the list is empty.
+const PUBLIC_IP_PROVIDERS_FALLBACK: &str = "ipify";
+/// The number of providers that must return the same public IP address.
+const PUBLIC_IP_CONSENSUS: &str = env!("PUBLIC_IP_CONSENSUS");
+/// The fallback number of providers that must return the same public IP address.
+const PUBLIC_IP_CONSENSUS_FALLBACK: usize = 1;
+
+/// The interface ID of the host whose IPv6 address is published, such as `::1234:5678:9abc:def0`.
+/// The device publishes its own IPv6 address if it is empty.
+const DNS_IPV6_SUFFIX: &str = env!("DNS_IPV6_SUFFIX");
+
+/// The maximum number of providers that are queried.
+const MAX_PROVIDERS: usize = 8;
+/// The buffer size for the requests to the providers.
+const REQUEST_BUFFER_SIZE: usize = 256;
+
+/// The prefix of the STUN servers in the list of providers.
+const STUN_PREFIX: &str = "stun:";
+
+/// The known providers of the public IP address and their URL, which only return IPv4 addresses.
+const KNOWN_PROVIDERS: [(&str, &str); 5] = [
+    ("ipify", "api.ipify.org/"),
+    ("icanhazip", "ipv4.icanhazip.com/"),
+    ("ifconfig.co", "ifconfig.co/ip"),
+    ("google-stun", "stun:stun.l.google.com:19302"),
+    ("cloudflare-stun", "stun:stun.cloudflare.com:3478"),
+];
+
+/// A provider of the public IP address.
+#[derive(Debug, Clone, Copy)]
+enum Provider {
+    /// An HTTP service returning the address as plain text at the path.
+    Http {
+        host: &'static str,
+        path: &'static str,
+    },
+    /// A STUN server answering binding requests on UDP.
+    Stun { host: &'static str, port: u16 },
+    /// The default gateway, asked for its external address with NAT-PMP.
+    NatPmp,
+    /// The default gateway, asked for its external address with UPnP IGD.
+    Upnp,
+}
+
+/// Get the public IP address from the providers.
+/// The providers are queried in order until enough of them return the same address.
+///
+/// Returns the delay asked by the providers with a `Retry-After` header on failure, if any.
+pub async fn get_public_ip(stack: Stack<'_>, rng: Rng) -> Result<IpAddress, Option<u64>> {
+    let providers = get_providers();
+    let consensus = get_consensus(providers.len());
+
+    let mut answers = Vec::<(IpAddress, usize), MAX_PROVIDERS>::new();
+    let mut retry_after = Some(u64::MAX);
+    for provider in providers {
+        let ip = match query_provider(stack, provider, rng).await {
+            Ok(v) => v,
+            Err(v) => {
+                retry_after = retry_after.min(v);
+                continue;
+            }
+        };
+
+        let votes = match answers.iter_mut().find(|(v, _)| *v == ip) {
+            Some((_, votes)) => {
+                *votes += 1;
+                *votes
+            }
+            None => {
+                // There is one answer per provider at most, so it always fits
+                let _ = answers.push((ip, 1));
+                1
+            }
+        };
+        if votes >= consensus {
+            return Ok(ip);
+        }
+    }
+
+    if answers.is_empty() {
+        log::error!("DNS | No public IP provider answered");
+        Err(retry_after.filter(|v| *v != u64::MAX))
+    } else {
+        log::error!(
+            "DNS | Public IP providers did not agree on {} addresses",
+            consensus
+        );
+        Err(None)
+    }
+}
+
+/// Get the public IPv6 address to publish from the global IPv6 address of the device.
+/// The interface ID of the device is replaced by `DNS_IPV6_SUFFIX` if it is set, so that
+/// another host of the network can be published, as they all share the same /64 prefix.
+pub fn get_public_ipv6(stack: Stack<'_>) -> Result<Ipv6Addr, ()> {
+    let Some(address) = get_global_ipv6(stack) else {
+        log::warn!("DNS | Device has no global IPv6 address yet");
+        return Err(());
+    };
+
+    let suffix = DNS_IPV6_SUFFIX.trim();
+    if suffix.is_empty() {
+        return Ok(address);
+    }
+    let suffix = match suffix.parse::<Ipv6Addr>() {
+        Ok(v) => v,
+        Err(_) => {
+            log::error!(
+                "DNS | Error parsing DNS_IPV6_SUFFIX to an IPv6 address: {}",
+                suffix
+            );
+            return Err(());
+        }
+    };
+    let mut octets = address.octets();
+    octets[8..].copy_from_slice(&suffix.octets()[8..]);
+    Ok(Ipv6Addr::from(octets))
+}
+
+/// Query a provider for the public IP address and check that the address is public.
+/// Returns the delay asked with a `Retry-After` header on failure, if any.
+async fn query_provider(
+    stack: Stack<'_>,
+    provider: Provider,
+    rng: Rng,
+) -> Result<IpAddress, Option<u64>> {
+    let (host, ip) = match provider {
+        Provider::Http { host, path } => (host, query_http(stack, host, path).await?),
+        Provider::Stun { host, port } => (
+            host,
+            stun::query_server(stack, host, port, rng)
+                .await
+                .map_err(|_| None)?,
+        ),
+        Provider::NatPmp => (
+            "NAT-PMP gateway",
+            nat_pmp::query_gateway(stack, get_gateway(stack)?, rng)
+                .await
+                .map_err(|_| None)?,
+        ),
+        Provider::Upnp => (
+            "UPnP gateway",
+            upnp::query_gateway(stack, get_gateway(stack)?)
+                .await
+                .map_err(|_| None)?,
+        ),
+    };
+
+    // A private address means that the provider is not reached through the internet
+    if !matches!(ip, IpAddress::Ipv4(_)) || !is_public_ip_address(&ip) {
+        log::error!("DNS | {} returned a non-public IPv4 address: {}", host, ip);
+        return Err(None);
+    }
+
+    log::info!("DNS | Public IP address from {}: {}", host, ip);
+    Ok(ip)
+}
+
+/// Get the default gateway of the device, which is given by DHCP.
+fn get_gateway(stack: Stack<'_>) -> Result<Ipv4Addr, Option<u64>> {
+    match stack.config_v4().and_then(|v| v.gateway) {
+        Some(v) => Ok(v),
+        None => {
+            log::error!("DNS | Device has no default gateway");
+            Err(None)
+        }
+    }
+}
+
+/// Query an HTTP service for the public IP address.
+/// Returns the delay asked with a `Retry-After` header on failure, if any.
+async fn query_http(
+    stack: Stack<'_>,
+    host: &'static str,
+    path: &str,
+) -> Result<IpAddress, Option<u64>> {
+    let mut request = String::<REQUEST_BUFFER_SIZE>::new();
+    if write!(
+        request,
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: {}\r\nAccept: text/plain\r\nConnection: close\r\n\r\n",
+        path, host, USER_AGENT
+    )
+    .is_err()
+    {
+        log::error!("DNS | Public IP provider URL is too long: {}{}", host, path);
+        return Err(None);
+    }
+
+    let mut response_buf = [0; RESPONSE_BUFFER_SIZE];
+    let response = match send_http_request(stack, host, request.as_bytes(), &mut response_buf).await
+    {
+        Ok(v) => {
+            log::info!("DNS | Got response from {}", host);
+            v
+        }
+        Err(_) => return Err(None),
+    };
+
+    let Some(body) = response.text() else {
+        log::error!("DNS | Response from {} was not UTF8", host);
+        return Err(None);
+    };
+    if !response.is_success() {
+        log::error!(
+            "DNS | Public IP provider {} returned an error: {}",
+            host,
+            response.status
+        );
+        log::error!("{}", body);
+        return Err(response.retry_after());
+    }
+
+    // Remove the line ending of the body
+    parse_ip_address(body.trim()).map_err(|e| {
+        log::error!("DNS | Public IP address not found in response -> {}", e);
+        log::error!("{}", body);
+        None
+    })
+}
+
+/// Get the providers in the configured list.
+fn get_providers() -> Vec<Provider, MAX_PROVIDERS> {
+    let mut providers = Vec::new();
+    for name in PUBLIC_IP_PROVIDERS
+        .split(',')
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+    {
+        let Some(provider) = get_provider(name) else {
+            continue;
+        };
+        if providers.push(provider).is_err() {
+            log::warn!(
+                "DNS | Only the first {} public IP providers are used",
+                MAX_PROVIDERS
+            );
+            break;
+        }
+    }
+
+    if providers.is_empty() {
+        log::error!("DNS | No public IP provider was configured");
+        log::error!(
+            "DNS | Using fallback public IP provider: {}",
+            PUBLIC_IP_PROVIDERS_FALLBACK
+        );
+        providers.extend(get_provider(PUBLIC_IP_PROVIDERS_FALLBACK));
+    }
+    providers
+}
+
+/// Get a provider from its name, its `host/path` URL or its `stun:host:port` address.
+/// Returns `None` if the port of the STUN server is invalid.
+fn get_provider(name: &'static str) -> Option<Provider> {
+    match name {
+        "natpmp" => return Some(Provider::NatPmp),
+        "upnp" => return Some(Provider::Upnp),
+        _ => (),
+    }
+    let url = KNOWN_PROVIDERS
+        .iter()
+        .find(|(known, _)| *known == name)
+        .map_or(name, |(_, url)| *url);
+
+    if let Some(address) = url.strip_prefix(STUN_PREFIX) {
+        let (host, port) = match address.split_once(':') {
+            Some((host, port)) => (host, port.parse::<u16>().ok()),
+            None => (address, Some(STUN_PORT)),
+        };
+        return match port {
+            Some(port) => Some(Provider::Stun { host, port }),
+            None => {
+                log::error!("DNS | Invalid port of STUN server: {}", name);
+                None
+            }
+        };
+    }
+    match url.find('/') {
+        Some(i) => Some(Provider::Http {
+            host: &url[..i],
+            path: &url[i..],
+        }),
+        None => Some(Provider::Http {
+            host: url,
+            path: "/",
+        }),
+    }
+}
+
+/// Parse the number of providers that must agree and fallback to the fallback number if there is an error.
+fn get_consensus(provider_count: usize) -> usize {
+    let consensus = match PUBLIC_IP_CONSENSUS.parse::<usize>() {
+        Ok(v) if v > 0 => v,
+        _ => {
+            log::error!(
+                "DNS | Error parsing PUBLIC_IP_CONSENSUS to a positive number: {}",
+                PUBLIC_IP_CONSENSUS
+            );
+            log::error!(
+                "DNS | Using fallback public IP consensus: {}",
+                PUBLIC_IP_CONSENSUS_FALLBACK
+            );
+            PUBLIC_IP_CONSENSUS_FALLBACK
+        }
+    };
+
+    if consensus > provider_count {
+        log::warn!(
+            "DNS | PUBLIC_IP_CONSENSUS is capped to the {} public IP providers",
+            provider_count
+        );
+        return provider_count;
+    }
+    consensus
+}
@@ -0,0 +1,173 @@
+use super::provider::PROVIDER;
+use crate::flash::Partition;
+use core::net::{Ipv4Addr, Ipv6Addr};
+use embassy_net::IpAddress;
+
+/// The label of the data partition storing the published addresses, see `partitions.csv`.
+const PARTITION_LABEL: &str = "wakesp";
+/// The first word of the stored addresses, which changes with their format.
+const MAGIC: u32 = u32::from_le_bytes(*b"WKS2");
+/// The number of words of the stored addresses: the magic, an entry per family, the stop and a checksum.
+const STORAGE_WORDS: usize = 1 + 2 * ENTRY_WORDS + 1 + 1;
+/// The index of the word storing the key of the configuration for which the updates are stopped, or 0.
+const STOP_WORD: usize = 1 + 2 * ENTRY_WORDS;
+/// The number of words of an entry: the key of the record, the family and the address.
+const ENTRY_WORDS: usize = 6;
+
+/// The addresses last accepted by the provider for each record.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Published {
+    pub ip: Option<Ipv4Addr>,
+    pub ipv6: Option<Ipv6Addr>,
+    /// Whether the updates are stopped after a fatal error, until the configuration changes.
+    pub stopped: bool,
+}
+
+impl Published {
+    /// Get the address last published for the family of the given address.
+    pub fn get(&self, family: &IpAddress) -> Option<IpAddress> {
+        match family {
+            IpAddress::Ipv4(_) => self.ip.map(IpAddress::Ipv4),
+            IpAddress::Ipv6(_) => self.ipv6.map(IpAddress::Ipv6),
+        }
+    }
+
+    /// Set the address last published for its family.
+    pub fn set(&mut self, ip: IpAddress) {
+        match ip {
+            IpAddress::Ipv4(v) => self.ip = Some(v),
+            IpAddress::Ipv6(v) => self.ipv6 = Some(v),
+        }
+    }
+}
+
+/// Load the published addresses from flash.
+/// The address of a record is dropped if the record was changed in the configuration since it was saved,
+/// and the updates are started again if the configuration was changed since they were stopped.
+pub fn load() -> Published {
+    let mut words = [0u32; STORAGE_WORDS];
+    let Some(partition) = Partition::find(PARTITION_LABEL) else {
+        log::warn!("DNS | Published addresses cannot be saved without their partition");
+        return Published::default();
+    };
+    if read_words(&partition, &mut words).is_err()
+        || words[0] != MAGIC
+        || words[STORAGE_WORDS - 1] != checksum(&words[..STORAGE_WORDS - 1])
+    {
+        log::info!("DNS | No published address saved in flash");
+        return Published::default();
+    }
+
+    let mut published = Published::default();
+    for entry in words[1..STOP_WORD].chunks(ENTRY_WORDS) {
+        let mut octets = [0u8; 16];
+        for (chunk, word) in octets.chunks_mut(4).zip(&entry[2..]) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        match entry[1] {
+            4 if entry[0] == record_key(false) => {
+                published.ip = Some(Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3]))
+            }
+            6 if entry[0] == record_key(true) => published.ipv6 = Some(Ipv6Addr::from(octets)),
+            _ => (),
+        }
+    }
+    log::info!(
+        "DNS | Published addresses loaded from flash: {:?}, {:?}",
+        published.ip,
+        published.ipv6
+    );
+    published.stopped = words[STOP_WORD] == configuration_key();
+    published
+}
+
+/// Save the published addresses to flash, unless they are already saved.
+pub fn save(published: &Published) {
+    let mut words = [0u32; STORAGE_WORDS];
+    words[0] = MAGIC;
+    let entries = [
+        published.ip.map(|v| {
+            let mut octets = [0u8; 16];
+            octets[..4].copy_from_slice(&v.octets());
+            (record_key(false), 4, octets)
+        }),
+        published.ipv6.map(|v| (record_key(true), 6, v.octets())),
+    ];
+    for (entry, value) in words[1..STOP_WORD].chunks_mut(ENTRY_WORDS).zip(entries) {
+        let Some((key, family, octets)) = value else {
+            continue;
+        };
+        entry[0] = key;
+        entry[1] = family;
+        for (word, chunk) in entry[2..].iter_mut().zip(octets.chunks(4)) {
+            *word = u32::from_be_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+        }
+    }
+    if published.stopped {
+        words[STOP_WORD] = configuration_key();
+    }
+    words[STORAGE_WORDS - 1] = checksum(&words[..STORAGE_WORDS - 1]);
+
+    // Each write erases the sector, so skip it when nothing changed to spare the flash
+    let Some(partition) = Partition::find(PARTITION_LABEL) else {
+        return;
+    };
+    let mut saved = [0u32; STORAGE_WORDS];
+    if read_words(&partition, &mut saved).is_ok() && saved == words {
+        return;
+    }
+    let mut bytes = [0u8; STORAGE_WORDS * 4];
+    for (chunk, word) in bytes.chunks_mut(4).zip(words) {
+        chunk.copy_from_slice(&word.to_le_bytes());
+    }
+    if partition.write(0, &bytes).is_ok() {
+        log::info!("DNS | Published addresses saved to flash");
+    }
+}
+
+/// Read the words stored at the start of the partition.
+fn read_words(partition: &Partition, words: &mut [u32; STORAGE_WORDS]) -> Result<(), ()> {
+    let mut bytes = [0u8; STORAGE_WORDS * 4];
+    partition.read(0, &mut bytes)?;
+    for (word, chunk) in words.iter_mut().zip(bytes.chunks(4)) {
+        *word = u32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]);
+    }
+    Ok(())
+}
+
+/// Get the key of the record updated with the addresses of a family,
+/// so that the address saved for another record is not taken as published.
+fn record_key(ipv6: bool) -> u32 {
+    // The parts are separated by a null byte, so that moving a byte from one to the next changes the key
+    hash(
+        PROVIDER
+            .record_id(ipv6)
+            .iter()
+            .flat_map(|v| v.bytes().chain([0])),
+    )
+}
+
+/// Get the key of the whole configuration of the updates, including the credentials,
+/// so that updates stopped by an error of the configuration start again once it is fixed.
+fn configuration_key() -> u32 {
+    hash(
+        PROVIDER
+            .record_id(false)
+            .iter()
+            .chain(&PROVIDER.record_id(true))
+            .chain(&PROVIDER.credentials())
+            .flat_map(|v| v.bytes().chain([0])),
+    )
+}
+
+/// Compute the checksum of the words, which detects a sector that was never written or was partially written.
+fn checksum(words: &[u32]) -> u32 {
+    hash(words.iter().flat_map(|v| v.to_le_bytes()))
+}
+
+/// Compute the FNV-1a hash of the bytes.
+fn hash(bytes: impl IntoIterator<Item = u8>) -> u32 {
+    bytes.into_iter().fold(0x811c9dc5, |hash, byte| {
+        (hash ^ byte as u32).wrapping_mul(0x01000193)
+    })
+}
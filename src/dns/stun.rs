@@ -0,0 +1,106 @@
+mod message;
+
+use embassy_net::{
+    IpAddress, IpEndpoint, Stack,
+    dns::DnsQueryType,
+    udp::{PacketMetadata, UdpSocket},
+};
+use embassy_time::{Duration, with_timeout};
+use esp_hal::rng::Rng;
+use message::{binding_request, parse_response};
+
+/// The default port of the STUN servers.
+pub const STUN_PORT: u16 = 3478;
+/// The buffer size for the STUN messages.
+const UDP_BUFFER_SIZE: usize = 256;
+/// The number of times the request is sent when no response is received.
+const MAX_TRIES: u32 = 3;
+/// The time in milliseconds to wait for the first response, which doubles after each try.
+const RESPONSE_TIMEOUT: u64 = 1000;
+
+/// Get the public IP address of the device from a STUN server with a binding request (RFC 5389).
+pub async fn query_server(
+    stack: Stack<'_>,
+    host: &str,
+    port: u16,
+    mut rng: Rng,
+) -> Result<IpAddress, ()> {
+    // The request is sent over IPv4 to get the public IPv4 address
+    let server = match stack.dns_query(host, DnsQueryType::A).await {
+        Ok(v) if !v.is_empty() => IpEndpoint::new(v[0], port),
+        Ok(_) => {
+            log::error!("DNS | No IP found for STUN server {}", host);
+            return Err(());
+        }
+        Err(e) => {
+            log::error!("DNS | Error resolving STUN server {}: {:?}", host, e);
+            return Err(());
+        }
+    };
+
+    // The random transaction ID prevents other hosts from forging the response
+    let mut transaction_id = [0u8; 12];
+    rng.read(&mut transaction_id);
+    let request = binding_request(&transaction_id);
+
+    // Setup UDP socket
+    let mut rx_meta = [PacketMetadata::EMPTY; 2];
+    let mut rx_buffer = [0; UDP_BUFFER_SIZE];
+    let mut tx_meta = [PacketMetadata::EMPTY; 1];
+    let mut tx_buffer = [0; UDP_BUFFER_SIZE];
+    let mut socket = UdpSocket::new(
+        stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+    // Bind to a random local port
+    if let Err(e) = socket.bind(0) {
+        log::error!("DNS | Error binding UDP socket: {:?}", e);
+        return Err(());
+    }
+
+    // UDP is unreliable, so the request is sent again when no response is received
+    let mut buf = [0u8; UDP_BUFFER_SIZE];
+    let mut result = None;
+    for i in 0..MAX_TRIES {
+        log::info!("DNS | Sending STUN binding request to {}...", host);
+        if let Err(e) = socket.send_to(&request, server).await {
+            log::error!("DNS | Error sending STUN request to {}: {:?}", host, e);
+            break;
+        }
+
+        let timeout = Duration::from_millis(RESPONSE_TIMEOUT << i);
+        let response = with_timeout(timeout, async {
+            loop {
+                match socket.recv_from(&mut buf).await {
+                    Ok((len, meta)) if meta.endpoint == server => {
+                        if let Some(v) = parse_response(&buf[..len], &transaction_id) {
+                            break v;
+                        }
+                    }
+                    _ => continue,
+                }
+            }
+        })
+        .await;
+        if let Ok(v) = response {
+            result = Some(v);
+            break;
+        }
+    }
+    socket.close();
+
+    match result {
+        Some(Ok(v)) => Ok(v.into()),
+        Some(Err(code)) => {
+            log::error!("DNS | STUN server {} returned an error: {}", host, code);
+            Err(())
+        }
+        None => {
+            log::error!("DNS | No response from STUN server {}", host);
+            Err(())
+        }
+    }
+}
@@ -0,0 +1,235 @@
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// The size of the header of a STUN message.
+const HEADER_SIZE: usize = 20;
+/// The value in all the STUN messages that tells them apart from the other protocols.
+const MAGIC_COOKIE: u32 = 0x2112a442;
+/// The message types of the binding requests and of their responses.
+const BINDING_REQUEST: u16 = 0x0001;
+const BINDING_SUCCESS: u16 = 0x0101;
+const BINDING_ERROR: u16 = 0x0111;
+/// The attribute types of the address of the client as seen by the server.
+/// The XOR-MAPPED-ADDRESS is preferred, as some routers rewrite the addresses they find in the packets.
+const MAPPED_ADDRESS: u16 = 0x0001;
+const XOR_MAPPED_ADDRESS: u16 = 0x0020;
+/// The attribute type of the error code of an error response.
+const ERROR_CODE: u16 = 0x0009;
+
+/// Build a binding request without attributes with the given transaction ID.
+pub fn binding_request(transaction_id: &[u8; 12]) -> [u8; HEADER_SIZE] {
+    let mut request = [0u8; HEADER_SIZE];
+    request[0..2].copy_from_slice(&BINDING_REQUEST.to_be_bytes());
+    request[4..8].copy_from_slice(&MAGIC_COOKIE.to_be_bytes());
+    request[8..].copy_from_slice(transaction_id);
+    request
+}
+
+/// Parse the response to the binding request with the given transaction ID.
+/// Returns `None` if the message is not the response, or the error code if it is an error response.
+pub fn parse_response(message: &[u8], transaction_id: &[u8]) -> Option<Result<IpAddr, u16>> {
+    let read_u16 = |i: usize| Some(u16::from_be_bytes([*message.get(i)?, *message.get(i + 1)?]));
+    let message_type = read_u16(0)?;
+    let len = read_u16(2)? as usize;
+    if message.get(4..8)? != MAGIC_COOKIE.to_be_bytes()
+        || message.get(8..HEADER_SIZE)? != transaction_id
+        || !matches!(message_type, BINDING_SUCCESS | BINDING_ERROR)
+    {
+        return None;
+    }
+    let mut attributes = message.get(HEADER_SIZE..HEADER_SIZE + len)?;
+
+    let mut mapped = None;
+    while attributes.len() >= 4 {
+        let attribute_type = u16::from_be_bytes([attributes[0], attributes[1]]);
+        let value_len = u16::from_be_bytes([attributes[2], attributes[3]]) as usize;
+        let value = attributes.get(4..4 + value_len)?;
+        // The attributes are padded to a multiple of 4 bytes
+        attributes = attributes
+            .get((4 + value_len).next_multiple_of(4)..)
+            .unwrap_or(&[]);
+
+        match attribute_type {
+            ERROR_CODE if message_type == BINDING_ERROR && value.len() >= 4 => {
+                return Some(Err((value[2] & 0x07) as u16 * 100 + value[3] as u16));
+            }
+            XOR_MAPPED_ADDRESS => {
+                if let Some(v) = parse_address(value, Some(&message[4..HEADER_SIZE])) {
+                    return Some(Ok(v));
+                }
+            }
+            MAPPED_ADDRESS => mapped = mapped.or(parse_address(value, None)),
+            _ => (),
+        }
+    }
+    match message_type {
+        BINDING_ERROR => Some(Err(0)),
+        _ => mapped.map(Ok),
+    }
+}
+
+/// Parse the address of a MAPPED-ADDRESS attribute, or of a XOR-MAPPED-ADDRESS attribute
+/// whose address is XORed with the magic cookie and the transaction ID given in `xor`.
+fn parse_address(value: &[u8], xor: Option<&[u8]>) -> Option<IpAddr> {
+    let mut octets = [0u8; 16];
+    let len = match value.get(1)? {
+        1 => 4,
+        2 => 16,
+        _ => return None,
+    };
+    octets[..len].copy_from_slice(value.get(4..4 + len)?);
+    if let Some(xor) = xor {
+        octets.iter_mut().zip(xor).for_each(|(v, x)| *v ^= x);
+    }
+    match len {
+        4 => Some(IpAddr::V4(Ipv4Addr::new(
+            octets[0], octets[1], octets[2], octets[3],
+        ))),
+        _ => Some(IpAddr::V6(Ipv6Addr::from(octets))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The transaction ID of the sample responses of RFC 5769.
+    const TRANSACTION_ID: [u8; 12] = [
+        0xb7, 0xe7, 0xa7, 0x01, 0xbc, 0x34, 0xd6, 0x86, 0xfa, 0x87, 0xdf, 0xae,
+    ];
+    /// The XOR-MAPPED-ADDRESS of 192.0.2.1 port 32853 in the IPv4 sample response of RFC 5769.
+    const XOR_IPV4: [u8; 8] = [0x00, 0x01, 0xa1, 0x47, 0xe1, 0x12, 0xa6, 0x43];
+    /// The XOR-MAPPED-ADDRESS of 2001:db8:1234:5678:11:2233:4455:6677 port 32853
+    /// in the IPv6 sample response of RFC 5769.
+    const XOR_IPV6: [u8; 20] = [
+        0x00, 0x02, 0xa1, 0x47, 0x01, 0x13, 0xa9, 0xfa, 0xa5, 0xd3, 0xf1, 0x79, 0xbc, 0x25, 0xf4,
+        0xb5, 0xbe, 0xd2, 0xb9, 0xd9,
+    ];
+    /// The MAPPED-ADDRESS of 198.51.100.7 port 3478, which is not XORed.
+    const MAPPED_IPV4: [u8; 8] = [0x00, 0x01, 0x0d, 0x96, 198, 51, 100, 7];
+
+    /// Build a message of the given type with the attributes, padding their values.
+    fn message(message_type: u16, attributes: &[(u16, &[u8])]) -> Vec<u8> {
+        let mut message = binding_request(&TRANSACTION_ID).to_vec();
+        message[0..2].copy_from_slice(&message_type.to_be_bytes());
+        for (attribute_type, value) in attributes {
+            message.extend_from_slice(&attribute_type.to_be_bytes());
+            message.extend_from_slice(&(value.len() as u16).to_be_bytes());
+            message.extend_from_slice(value);
+            message.resize(message.len().next_multiple_of(4), 0);
+        }
+        let len = (message.len() - HEADER_SIZE) as u16;
+        message[2..4].copy_from_slice(&len.to_be_bytes());
+        message
+    }
+
+    fn ipv4(a: u8, b: u8, c: u8, d: u8) -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(a, b, c, d))
+    }
+
+    #[test]
+    fn writes_binding_request() {
+        let request = binding_request(&TRANSACTION_ID);
+        assert_eq!(
+            request[..8],
+            [0x00, 0x01, 0x00, 0x00, 0x21, 0x12, 0xa4, 0x42]
+        );
+        assert_eq!(request[8..], TRANSACTION_ID);
+    }
+
+    #[test]
+    fn parses_xor_mapped_ipv4_address() {
+        let response = message(BINDING_SUCCESS, &[(XOR_MAPPED_ADDRESS, &XOR_IPV4)]);
+        assert_eq!(
+            parse_response(&response, &TRANSACTION_ID),
+            Some(Ok(ipv4(192, 0, 2, 1)))
+        );
+    }
+
+    #[test]
+    fn parses_xor_mapped_ipv6_address() {
+        let response = message(BINDING_SUCCESS, &[(XOR_MAPPED_ADDRESS, &XOR_IPV6)]);
+        let ip: Ipv6Addr = "2001:db8:1234:5678:11:2233:4455:6677".parse().unwrap();
+        assert_eq!(
+            parse_response(&response, &TRANSACTION_ID),
+            Some(Ok(IpAddr::V6(ip)))
+        );
+    }
+
+    #[test]
+    fn prefers_xor_mapped_address() {
+        let response = message(
+            BINDING_SUCCESS,
+            &[
+                (MAPPED_ADDRESS, &MAPPED_IPV4),
+                (XOR_MAPPED_ADDRESS, &XOR_IPV4),
+            ],
+        );
+        assert_eq!(
+            parse_response(&response, &TRANSACTION_ID),
+            Some(Ok(ipv4(192, 0, 2, 1)))
+        );
+    }
+
+    #[test]
+    fn falls_back_to_mapped_address() {
+        // The unknown attribute of 5 bytes is padded before the address
+        let response = message(
+            BINDING_SUCCESS,
+            &[(0x8022, b"wakes"), (MAPPED_ADDRESS, &MAPPED_IPV4)],
+        );
+        assert_eq!(
+            parse_response(&response, &TRANSACTION_ID),
+            Some(Ok(ipv4(198, 51, 100, 7)))
+        );
+        let response = message(BINDING_SUCCESS, &[]);
+        assert_eq!(parse_response(&response, &TRANSACTION_ID), None);
+    }
+
+    #[test]
+    fn returns_error_code() {
+        // 420 Unknown Attribute: class 4 and number 20
+        let response = message(BINDING_ERROR, &[(ERROR_CODE, b"\x00\x00\x04\x14Unknown")]);
+        assert_eq!(parse_response(&response, &TRANSACTION_ID), Some(Err(420)));
+        let response = message(BINDING_ERROR, &[]);
+        assert_eq!(parse_response(&response, &TRANSACTION_ID), Some(Err(0)));
+    }
+
+    #[test]
+    fn ignores_other_transactions() {
+        let response = message(BINDING_SUCCESS, &[(XOR_MAPPED_ADDRESS, &XOR_IPV4)]);
+        let mut transaction_id = TRANSACTION_ID;
+        transaction_id[11] ^= 1;
+        assert_eq!(parse_response(&response, &transaction_id), None);
+    }
+
+    #[test]
+    fn ignores_bad_magic_cookie() {
+        let mut response = message(BINDING_SUCCESS, &[(XOR_MAPPED_ADDRESS, &XOR_IPV4)]);
+        response[4] ^= 1;
+        assert_eq!(parse_response(&response, &TRANSACTION_ID), None);
+    }
+
+    #[test]
+    fn ignores_other_messages() {
+        let request = binding_request(&TRANSACTION_ID);
+        assert_eq!(parse_response(&request, &TRANSACTION_ID), None);
+        assert_eq!(parse_response(&request[..12], &TRANSACTION_ID), None);
+    }
+
+    #[test]
+    fn rejects_truncated_attributes() {
+        let response = message(BINDING_SUCCESS, &[(XOR_MAPPED_ADDRESS, &XOR_IPV4)]);
+        // The message is shorter than its length
+        let len = response.len();
+        assert_eq!(parse_response(&response[..len - 4], &TRANSACTION_ID), None);
+
+        // The value is longer than the attributes
+        let mut response = response;
+        response[HEADER_SIZE + 3] = 12;
+        assert_eq!(parse_response(&response, &TRANSACTION_ID), None);
+
+        // The value is shorter than the address
+        let response = message(BINDING_SUCCESS, &[(XOR_MAPPED_ADDRESS, &XOR_IPV4[..6])]);
+        assert_eq!(parse_response(&response, &TRANSACTION_ID), None);
+    }
+}
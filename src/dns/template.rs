@@ -0,0 +1,156 @@
+use core::{fmt::Write, net::Ipv6Addr};
+use embassy_net::IpAddress;
+use heapless::String;
+
+/// A placeholder of a request template, written as `{name}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Placeholder {
+    /// The public IPv4 address.
+    Ip,
+    /// The public IPv6 address.
+    Ipv6,
+    /// The hostname of the device.
+    Hostname,
+}
+
+impl Placeholder {
+    const ALL: [Placeholder; 3] = [Placeholder::Ip, Placeholder::Ipv6, Placeholder::Hostname];
+
+    /// The name of the placeholder, between the braces.
+    const fn name(self) -> &'static str {
+        match self {
+            Placeholder::Ip => "ip",
+            Placeholder::Ipv6 => "ipv6",
+            Placeholder::Hostname => "hostname",
+        }
+    }
+
+    /// The maximum length of the value replacing the placeholder.
+    const fn max_len(self) -> usize {
+        match self {
+            Placeholder::Ip => "255.255.255.255".len(),
+            Placeholder::Ipv6 => "ffff:ffff:ffff:ffff:ffff:ffff:ffff:ffff".len(),
+            // Limit from embassy_net
+            Placeholder::Hostname => 32,
+        }
+    }
+}
+
+/// The values replacing the placeholders of a request template.
+/// Unknown values are replaced by an empty string.
+pub struct Values<'a> {
+    pub ip: Option<IpAddress>,
+    pub ipv6: Option<Ipv6Addr>,
+    pub hostname: &'a str,
+}
+
+/// Check at compile time that the template only contains known placeholders
+/// and that it always fits in `capacity` bytes once they are replaced.
+pub const fn validate(template: &str, capacity: usize) {
+    let bytes = template.as_bytes();
+    let mut len = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        match placeholder_at(bytes, i) {
+            Some((Ok(placeholder), end)) => {
+                len += placeholder.max_len();
+                i = end;
+            }
+            Some((Err(_), _)) => {
+                panic!("Unknown placeholder in request, expected {{ip}}, {{ipv6}} or {{hostname}}")
+            }
+            None => {
+                len += 1;
+                i += 1;
+            }
+        }
+    }
+
+    if len > capacity {
+        panic!("Request does not fit in buffer once its placeholders are replaced")
+    }
+}
+
+/// Replace the placeholders of the template with their values.
+pub fn render<const N: usize>(template: &str, values: &Values<'_>) -> Result<String<N>, ()> {
+    let bytes = template.as_bytes();
+    let mut rendered = String::new();
+    let mut text_start = 0;
+    let mut i = 0;
+    while i < bytes.len() {
+        let Some((placeholder, end)) = placeholder_at(bytes, i) else {
+            i += 1;
+            continue;
+        };
+
+        rendered.push_str(&template[text_start..i])?;
+        match placeholder? {
+            Placeholder::Ip => {
+                if let Some(ip) = values.ip {
+                    write!(rendered, "{}", ip).map_err(|_| ())?;
+                }
+            }
+            Placeholder::Ipv6 => {
+                if let Some(ip) = values.ipv6 {
+                    write!(rendered, "{}", ip).map_err(|_| ())?;
+                }
+            }
+            Placeholder::Hostname => rendered.push_str(values.hostname)?,
+        }
+        i = end;
+        text_start = end;
+    }
+    rendered.push_str(&template[text_start..])?;
+
+    Ok(rendered)
+}
+
+/// Find the placeholder starting at index `start` of the template.
+/// A placeholder is a name made of lowercase letters, digits and underscores between braces,
+/// so that other braces, such as the ones of a JSON body, are kept as is.
+///
+/// Returns the placeholder, which is an error if its name is unknown, and the index following it.
+const fn placeholder_at(template: &[u8], start: usize) -> Option<(Result<Placeholder, ()>, usize)> {
+    if template[start] != b'{' {
+        return None;
+    }
+
+    let mut end = start + 1;
+    while end < template.len() && is_name_byte(template[end]) {
+        end += 1;
+    }
+    if end == start + 1 || end >= template.len() || template[end] != b'}' {
+        return None;
+    }
+
+    let (_, name) = template.split_at(start + 1);
+    let (name, _) = name.split_at(end - start - 1);
+    let mut i = 0;
+    while i < Placeholder::ALL.len() {
+        if bytes_eq(Placeholder::ALL[i].name().as_bytes(), name) {
+            return Some((Ok(Placeholder::ALL[i]), end + 1));
+        }
+        i += 1;
+    }
+    Some((Err(()), end + 1))
+}
+
+/// Check if a byte can be part of the name of a placeholder.
+const fn is_name_byte(byte: u8) -> bool {
+    byte.is_ascii_lowercase() || byte.is_ascii_digit() || byte == b'_'
+}
+
+/// Compare two byte slices in a const context.
+const fn bytes_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    let mut i = 0;
+    while i < a.len() {
+        if a[i] != b[i] {
+            return false;
+        }
+        i += 1;
+    }
+    true
+}
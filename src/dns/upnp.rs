@@ -0,0 +1,259 @@
+mod description;
+
+use super::{RESPONSE_BUFFER_SIZE, USER_AGENT};
+use crate::{
+    http_client::{Response, Transport, send_request},
+    utils::parse_ip_address,
+};
+use alloc::vec;
+use core::{cell::RefCell, fmt::Write, net::Ipv4Addr};
+use description::{find_connection_service, resolve_control_url, split_url, tag_value};
+use embassy_net::{
+    IpAddress, IpEndpoint, Stack,
+    udp::{PacketMetadata, UdpSocket},
+};
+use embassy_sync::blocking_mutex::{CriticalSectionMutex, Mutex, raw::CriticalSectionRawMutex};
+use embassy_time::{Duration, with_timeout};
+use heapless::String;
+
+/// The multicast address and port of the SSDP discovery requests.
+const SSDP_ENDPOINT: IpEndpoint =
+    IpEndpoint::new(IpAddress::Ipv4(Ipv4Addr::new(239, 255, 255, 250)), 1900);
+/// The SSDP request searching for the internet gateway devices of the network.
+const SSDP_SEARCH: &str = "M-SEARCH * HTTP/1.1\r\nHOST: 239.255.255.250:1900\r\nMAN: \"ssdp:discover\"\r\nMX: 2\r\nST: urn:schemas-upnp-org:device:InternetGatewayDevice:1\r\n\r\n";
+/// The time in seconds to wait for the answer of the gateway, which waits up to the MX value before answering.
+const SSDP_TIMEOUT: u64 = 3;
+/// The buffer size for the SSDP messages.
+const UDP_BUFFER_SIZE: usize = 512;
+/// The buffer size for the device description, which lists all the services of the gateway.
+/// It is allocated on the heap, as it is only needed during the discovery.
+const DESCRIPTION_BUFFER_SIZE: usize = 8192;
+/// The buffer size for the requests to the gateway.
+const REQUEST_BUFFER_SIZE: usize = 768;
+/// The buffer size for the URL of the description and the path of the control URL.
+const URL_SIZE: usize = 128;
+/// The buffer size for the type of the connection service.
+const SERVICE_TYPE_SIZE: usize = 64;
+
+/// The connection service of the gateway found during the discovery.
+#[derive(Debug, Clone)]
+struct Control {
+    /// The gateway the service was found on.
+    gateway: Ipv4Addr,
+    /// The port of the control URL.
+    port: u16,
+    /// The path of the control URL.
+    path: String<URL_SIZE>,
+    /// The type of the service, which is also the namespace of its actions.
+    service: String<SERVICE_TYPE_SIZE>,
+}
+
+/// The connection service found during the last discovery, reused until a request fails.
+static CONTROL: Mutex<CriticalSectionRawMutex, RefCell<Option<Control>>> =
+    CriticalSectionMutex::new(RefCell::new(None));
+
+/// Get the external address of the gateway with the `GetExternalIPAddress` action of UPnP IGD.
+pub async fn query_gateway(stack: Stack<'_>, gateway: Ipv4Addr) -> Result<IpAddress, ()> {
+    let cached = CONTROL.lock(|v| v.borrow().clone().filter(|v| v.gateway == gateway));
+    let control = match cached {
+        Some(v) => v,
+        None => {
+            let v = discover(stack, gateway).await?;
+            CONTROL.lock(|control| *control.borrow_mut() = Some(v.clone()));
+            v
+        }
+    };
+
+    let result = get_external_address(stack, &control).await;
+    if result.is_err() {
+        // The gateway may have restarted with another port, so discover it again at the next check
+        CONTROL.lock(|v| *v.borrow_mut() = None);
+    }
+    result
+}
+
+/// Find the connection service of the gateway with SSDP and its device description.
+async fn discover(stack: Stack<'_>, gateway: Ipv4Addr) -> Result<Control, ()> {
+    let location = search_gateway(stack, gateway).await?;
+    let Some((port, path)) = split_url(&location) else {
+        log::error!("DNS | Invalid UPnP description URL: {}", location);
+        return Err(());
+    };
+
+    let mut request = String::<REQUEST_BUFFER_SIZE>::new();
+    write!(
+        request,
+        "GET {} HTTP/1.1\r\nHost: {}:{}\r\nUser-Agent: {}\r\nConnection: close\r\n\r\n",
+        path, gateway, port, USER_AGENT
+    )
+    .map_err(|_| log::error!("DNS | UPnP description URL is too long: {}", location))?;
+
+    let mut response_buf = vec![0u8; DESCRIPTION_BUFFER_SIZE];
+    let response = send_gateway_request(stack, gateway, port, &request, &mut response_buf).await?;
+    let Some(description) = response.text() else {
+        log::error!("DNS | UPnP description of {} was not UTF8", gateway);
+        return Err(());
+    };
+
+    let Some((service, control_url)) = find_connection_service(description) else {
+        log::error!("DNS | Gateway {} has no UPnP connection service", gateway);
+        return Err(());
+    };
+    let (port, path) = resolve_control_url(port, control_url).ok_or_else(|| {
+        log::error!("DNS | Invalid UPnP control URL: {}", control_url);
+    })?;
+    log::info!(
+        "DNS | Found UPnP service {} of {} at port {} and path {}",
+        service,
+        gateway,
+        port,
+        path
+    );
+
+    Ok(Control {
+        gateway,
+        port,
+        path: String::try_from(path)
+            .map_err(|_| log::error!("DNS | UPnP control URL is too long: {}", path))?,
+        service: String::try_from(service)
+            .map_err(|_| log::error!("DNS | UPnP service type is too long: {}", service))?,
+    })
+}
+
+/// Send an SSDP search for the internet gateway devices and wait for the answer of the gateway.
+/// Returns the URL of the description of the gateway.
+async fn search_gateway(stack: Stack<'_>, gateway: Ipv4Addr) -> Result<String<URL_SIZE>, ()> {
+    // Setup UDP socket
+    let mut rx_meta = [PacketMetadata::EMPTY; 4];
+    let mut rx_buffer = [0; UDP_BUFFER_SIZE * 2];
+    let mut tx_meta = [PacketMetadata::EMPTY; 1];
+    let mut tx_buffer = [0; UDP_BUFFER_SIZE];
+    let mut socket = UdpSocket::new(
+        stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+    // Bind to a random local port
+    if let Err(e) = socket.bind(0) {
+        log::error!("DNS | Error binding UDP socket: {:?}", e);
+        return Err(());
+    }
+
+    log::info!("DNS | Searching for the UPnP gateway...");
+    if let Err(e) = socket.send_to(SSDP_SEARCH.as_bytes(), SSDP_ENDPOINT).await {
+        log::error!("DNS | Error sending SSDP search: {:?}", e);
+        return Err(());
+    }
+
+    // Only the gateway is trusted, so the answers of the other devices of the network are ignored
+    let mut buf = [0u8; UDP_BUFFER_SIZE];
+    let result = with_timeout(Duration::from_secs(SSDP_TIMEOUT), async {
+        loop {
+            match socket.recv_from(&mut buf).await {
+                Ok((len, meta)) if meta.endpoint.addr == IpAddress::Ipv4(gateway) => {
+                    let location = Response::parse(&mut buf[..len])
+                        .ok()
+                        .filter(|v| v.is_success())
+                        .and_then(|v| v.header("Location"))
+                        .and_then(|v| String::try_from(v).ok());
+                    if let Some(v) = location {
+                        break v;
+                    }
+                }
+                _ => continue,
+            }
+        }
+    })
+    .await;
+    socket.close();
+
+    result.map_err(|_| log::error!("DNS | No UPnP answer from gateway {}", gateway))
+}
+
+/// Call the `GetExternalIPAddress` action of the connection service.
+async fn get_external_address(stack: Stack<'_>, control: &Control) -> Result<IpAddress, ()> {
+    let mut body = String::<REQUEST_BUFFER_SIZE>::new();
+    let mut request = String::<REQUEST_BUFFER_SIZE>::new();
+    let written = write!(
+        body,
+        "<?xml version=\"1.0\"?>\r\n<s:Envelope xmlns:s=\"http://schemas.xmlsoap.org/soap/envelope/\" s:encodingStyle=\"http://schemas.xmlsoap.org/soap/encoding/\"><s:Body><u:GetExternalIPAddress xmlns:u=\"{}\"></u:GetExternalIPAddress></s:Body></s:Envelope>\r\n",
+        control.service
+    )
+    .and_then(|_| {
+        write!(
+            request,
+            "POST {} HTTP/1.1\r\nHost: {}:{}\r\nUser-Agent: {}\r\nContent-Type: text/xml; charset=\"utf-8\"\r\nSOAPAction: \"{}#GetExternalIPAddress\"\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            control.path,
+            control.gateway,
+            control.port,
+            USER_AGENT,
+            control.service,
+            body.len(),
+            body
+        )
+    });
+    if written.is_err() {
+        log::error!("DNS | UPnP request does not fit in buffer");
+        return Err(());
+    }
+
+    let mut response_buf = [0u8; RESPONSE_BUFFER_SIZE];
+    let response = send_gateway_request(
+        stack,
+        control.gateway,
+        control.port,
+        &request,
+        &mut response_buf,
+    )
+    .await?;
+    let Some(body) = response.text() else {
+        log::error!("DNS | UPnP response of {} was not UTF8", control.gateway);
+        return Err(());
+    };
+    if !response.is_success() {
+        log::error!(
+            "DNS | UPnP gateway {} returned an error: {}",
+            control.gateway,
+            response.status
+        );
+        log::error!("{}", body);
+        return Err(());
+    }
+
+    let Some(ip) = tag_value(body, "NewExternalIPAddress") else {
+        log::error!("DNS | External IP address not found in UPnP response");
+        log::error!("{}", body);
+        return Err(());
+    };
+    parse_ip_address(ip.trim()).map_err(|e| {
+        log::error!(
+            "DNS | Error parsing UPnP external IP address -> {}: {}",
+            e,
+            ip
+        );
+    })
+}
+
+/// Send a plain HTTP request to the gateway, which is on the local network.
+async fn send_gateway_request<'b>(
+    stack: Stack<'_>,
+    gateway: Ipv4Addr,
+    port: u16,
+    request: &str,
+    response_buf: &'b mut [u8],
+) -> Result<Response<'b>, ()> {
+    let mut host = String::<16>::new();
+    let _ = write!(host, "{}", gateway);
+    send_request(
+        stack,
+        &host,
+        port,
+        &Transport::Plain,
+        request.as_bytes(),
+        response_buf,
+        "DNS",
+    )
+    .await
+}
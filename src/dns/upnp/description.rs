@@ -0,0 +1,164 @@
+/// The prefixes of the types of the services giving the external address, for IP and PPP connections.
+const CONNECTION_SERVICES: [&str; 2] = [
+    "urn:schemas-upnp-org:service:WANIPConnection:",
+    "urn:schemas-upnp-org:service:WANPPPConnection:",
+];
+
+/// Find the first connection service in the device description.
+/// Returns the type of the service and its control URL.
+pub fn find_connection_service(description: &str) -> Option<(&str, &str)> {
+    description
+        .split("<service>")
+        .skip(1)
+        .filter_map(|service| {
+            let service_type = tag_value(service, "serviceType")?.trim();
+            let control_url = tag_value(service, "controlURL")?.trim();
+            Some((service_type, control_url))
+        })
+        .find(|(service_type, _)| {
+            CONNECTION_SERVICES
+                .iter()
+                .any(|v| service_type.starts_with(v))
+        })
+}
+
+/// Get the text of the first element with the given tag.
+pub fn tag_value<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let (start, _) = xml.match_indices('<').find(|(i, _)| {
+        xml[i + 1..]
+            .strip_prefix(tag)
+            .is_some_and(|v| v.starts_with('>'))
+    })?;
+    let value = &xml[start + tag.len() + 2..];
+    Some(&value[..value.find('<')?])
+}
+
+/// Split an `http://host:port/path` URL into its port and its path.
+/// The host is not returned, as the requests are only sent to the gateway.
+pub fn split_url(url: &str) -> Option<(u16, &str)> {
+    let url = url.trim().strip_prefix("http://")?;
+    let (authority, path) = match url.find('/') {
+        Some(i) => (&url[..i], &url[i..]),
+        None => (url, "/"),
+    };
+    let port = match authority.rsplit_once(':') {
+        Some((_, port)) => port.parse().ok()?,
+        None => 80,
+    };
+    Some((port, path))
+}
+
+/// Get the port and the path of the control URL of a service found in the description served on `port`.
+/// The control URL is either absolute or a path on the server of the description.
+pub fn resolve_control_url(port: u16, control_url: &str) -> Option<(u16, &str)> {
+    match control_url.starts_with('/') {
+        true => Some((port, control_url)),
+        false => split_url(control_url),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Build a device description with the given services: their type and control URL.
+    fn description(services: &[(&str, &str)]) -> String {
+        let mut xml = String::from(
+            "<?xml version=\"1.0\"?><root><device><deviceType>urn:schemas-upnp-org:device:WANConnectionDevice:1</deviceType><serviceList>",
+        );
+        for (service_type, control_url) in services {
+            xml += &format!(
+                "<service>\r\n<serviceType>{}</serviceType>\r\n<serviceId>id</serviceId>\r\n<controlURL> {} </controlURL>\r\n</service>",
+                service_type, control_url
+            );
+        }
+        xml + "</serviceList></device></root>"
+    }
+
+    #[test]
+    fn finds_ip_connection_service() {
+        let xml = description(&[
+            (
+                "urn:schemas-upnp-org:service:WANCommonInterfaceConfig:1",
+                "/ctl/CmnIfCfg",
+            ),
+            (
+                "urn:schemas-upnp-org:service:WANIPConnection:2",
+                "/ctl/IPConn",
+            ),
+        ]);
+        assert_eq!(
+            find_connection_service(&xml),
+            Some((
+                "urn:schemas-upnp-org:service:WANIPConnection:2",
+                "/ctl/IPConn"
+            ))
+        );
+    }
+
+    #[test]
+    fn finds_ppp_connection_service() {
+        let xml = description(&[(
+            "urn:schemas-upnp-org:service:WANPPPConnection:1",
+            "/upnp/control/WANPPPConn1",
+        )]);
+        assert_eq!(
+            find_connection_service(&xml),
+            Some((
+                "urn:schemas-upnp-org:service:WANPPPConnection:1",
+                "/upnp/control/WANPPPConn1"
+            ))
+        );
+    }
+
+    #[test]
+    fn ignores_other_services() {
+        let xml = description(&[(
+            "urn:schemas-upnp-org:service:Layer3Forwarding:1",
+            "/ctl/L3F",
+        )]);
+        assert_eq!(find_connection_service(&xml), None);
+        assert_eq!(find_connection_service(""), None);
+    }
+
+    #[test]
+    fn reads_tag_value() {
+        let xml = "<s:Body><NewExternalIPAddressX>1</NewExternalIPAddressX>\
+            <NewExternalIPAddress>203.0.113.5</NewExternalIPAddress></s:Body>";
+        assert_eq!(tag_value(xml, "NewExternalIPAddress"), Some("203.0.113.5"));
+        assert_eq!(tag_value(xml, "Missing"), None);
+        assert_eq!(tag_value("<a>unterminated", "a"), None);
+    }
+
+    #[test]
+    fn splits_url() {
+        assert_eq!(
+            split_url("http://192.168.1.1:5000/rootDesc.xml"),
+            Some((5000, "/rootDesc.xml"))
+        );
+        assert_eq!(
+            split_url(" http://192.168.1.1/igd.xml "),
+            Some((80, "/igd.xml"))
+        );
+        assert_eq!(split_url("http://192.168.1.1:49152"), Some((49152, "/")));
+        assert_eq!(split_url("https://192.168.1.1/igd.xml"), None);
+        assert_eq!(split_url("http://192.168.1.1:port/igd.xml"), None);
+    }
+
+    #[test]
+    fn resolves_relative_control_url() {
+        assert_eq!(
+            resolve_control_url(5000, "/ctl/IPConn"),
+            Some((5000, "/ctl/IPConn"))
+        );
+    }
+
+    #[test]
+    fn resolves_absolute_control_url() {
+        assert_eq!(
+            resolve_control_url(5000, "http://192.168.1.1:49152/upnp/control/WANIPConn1"),
+            Some((49152, "/upnp/control/WANIPConn1"))
+        );
+        assert_eq!(resolve_control_url(5000, "ctl/IPConn"), None);
+    }
+}
@@ -0,0 +1,140 @@
+mod message;
+
+use crate::utils::parse_ip_address;
+use embassy_net::{
+    IpAddress, IpEndpoint, Stack,
+    dns::DnsQueryType,
+    udp::{PacketMetadata, UdpSocket},
+};
+use embassy_time::{Duration, Instant, with_timeout};
+use message::{TYPE_A, TYPE_AAAA, parse_answers, write_query};
+
+/// The DDNS hostname resolved to check that the records were updated, or empty to disable the checks.
+pub const DNS_VERIFY_HOSTNAME: &str = env!("DNS_VERIFY_HOSTNAME");
+/// The address of the DNS server queried to resolve the hostname, such as an authoritative server of the zone.
+/// The DNS servers of the network are used if it is empty.
+const DNS_VERIFY_RESOLVER: &str = env!("DNS_VERIFY_RESOLVER");
+
+/// The port of the DNS servers.
+const DNS_PORT: u16 = 53;
+/// The buffer size for the DNS messages, which is the maximum size of a DNS message over UDP.
+const UDP_BUFFER_SIZE: usize = 512;
+/// The time in seconds to wait for the answer of the DNS server.
+const QUERY_TIMEOUT: u64 = 5;
+
+/// Check if the records are verified after they are updated.
+pub fn is_enabled() -> bool {
+    !DNS_VERIFY_HOSTNAME.is_empty()
+}
+
+/// Check if the hostname resolves to the address, among the addresses of its family.
+/// Returns an error if the hostname could not be resolved, in which case the result is unknown.
+pub async fn resolves_to(stack: Stack<'_>, ip: IpAddress) -> Result<bool, ()> {
+    let ipv6 = matches!(ip, IpAddress::Ipv6(_));
+    log::info!(
+        "DNS | Resolving {} to verify the record...",
+        DNS_VERIFY_HOSTNAME
+    );
+
+    if DNS_VERIFY_RESOLVER.is_empty() {
+        let query_type = match ipv6 {
+            true => DnsQueryType::Aaaa,
+            false => DnsQueryType::A,
+        };
+        return match stack.dns_query(DNS_VERIFY_HOSTNAME, query_type).await {
+            Ok(v) => {
+                log::info!("DNS | {} resolves to {:?}", DNS_VERIFY_HOSTNAME, v);
+                Ok(v.contains(&ip))
+            }
+            Err(e) => {
+                log::warn!("DNS | Error resolving {}: {:?}", DNS_VERIFY_HOSTNAME, e);
+                Err(())
+            }
+        };
+    }
+
+    let resolver = match parse_ip_address(DNS_VERIFY_RESOLVER) {
+        Ok(v) => v,
+        Err(e) => {
+            log::error!(
+                "DNS | Error parsing DNS_VERIFY_RESOLVER -> {}: {}",
+                e,
+                DNS_VERIFY_RESOLVER
+            );
+            return Err(());
+        }
+    };
+    query_resolver(stack, IpEndpoint::new(resolver, DNS_PORT), ip).await
+}
+
+/// Query the resolver for the addresses of the family of `ip` and check if it is one of them.
+async fn query_resolver(stack: Stack<'_>, resolver: IpEndpoint, ip: IpAddress) -> Result<bool, ()> {
+    let record_type = match ip {
+        IpAddress::Ipv4(_) => TYPE_A,
+        IpAddress::Ipv6(_) => TYPE_AAAA,
+    };
+    // The ID only needs to differ between consecutive queries
+    let id = Instant::now().as_ticks() as u16;
+    let mut query = [0u8; UDP_BUFFER_SIZE];
+    let query_len =
+        write_query(&mut query, id, DNS_VERIFY_HOSTNAME, record_type).ok_or_else(|| {
+            log::error!("DNS | Invalid hostname to verify: {}", DNS_VERIFY_HOSTNAME);
+        })?;
+
+    // Setup UDP socket
+    let mut rx_meta = [PacketMetadata::EMPTY; 1];
+    let mut rx_buffer = [0; UDP_BUFFER_SIZE];
+    let mut tx_meta = [PacketMetadata::EMPTY; 1];
+    let mut tx_buffer = [0; UDP_BUFFER_SIZE];
+    let mut socket = UdpSocket::new(
+        stack,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+    // Bind to a random local port
+    if let Err(e) = socket.bind(0) {
+        log::error!("DNS | Error binding UDP socket: {:?}", e);
+        return Err(());
+    }
+
+    if let Err(e) = socket.send_to(&query[..query_len], resolver).await {
+        log::warn!("DNS | Error sending DNS query to {}: {:?}", resolver, e);
+        return Err(());
+    }
+
+    // Other datagrams, like late answers to previous queries, are ignored
+    let mut buf = [0u8; UDP_BUFFER_SIZE];
+    let deadline = Duration::from_secs(QUERY_TIMEOUT);
+    let result = with_timeout(deadline, async {
+        loop {
+            match socket.recv_from(&mut buf).await {
+                Ok((len, meta)) if meta.endpoint == resolver => {
+                    if let Some(v) = parse_answers(&buf[..len], id, record_type, ip.into()) {
+                        break v;
+                    }
+                }
+                _ => continue,
+            }
+        }
+    })
+    .await;
+    socket.close();
+
+    match result {
+        Ok(Ok(v)) => Ok(v),
+        Ok(Err(rcode)) => {
+            log::warn!(
+                "DNS | DNS server {} answered with error {}",
+                resolver,
+                rcode
+            );
+            Err(())
+        }
+        Err(_) => {
+            log::warn!("DNS | No answer from DNS server {}", resolver);
+            Err(())
+        }
+    }
+}
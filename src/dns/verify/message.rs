@@ -0,0 +1,270 @@
+use core::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// The size of the header of a DNS message.
+const HEADER_SIZE: usize = 12;
+/// The flag of a query asking the server to resolve the name recursively.
+const RECURSION_DESIRED: u16 = 0x0100;
+/// The flag of a response.
+const RESPONSE_FLAG: u16 = 0x8000;
+/// The response code of a name that does not exist.
+const NAME_ERROR: u16 = 3;
+/// The record types of the IPv4 and IPv6 addresses.
+pub const TYPE_A: u16 = 1;
+pub const TYPE_AAAA: u16 = 28;
+/// The class of the internet records.
+const CLASS_IN: u16 = 1;
+
+/// Write a query for the records of the given type of the name.
+/// Returns the length of the query, or `None` if the name is invalid.
+pub fn write_query(buf: &mut [u8], id: u16, name: &str, record_type: u16) -> Option<usize> {
+    buf[..HEADER_SIZE].fill(0);
+    buf[0..2].copy_from_slice(&id.to_be_bytes());
+    buf[2..4].copy_from_slice(&RECURSION_DESIRED.to_be_bytes());
+    // One question
+    buf[4..6].copy_from_slice(&1u16.to_be_bytes());
+
+    let mut len = HEADER_SIZE;
+    for label in name.trim_end_matches('.').split('.') {
+        if label.is_empty() || label.len() > 63 {
+            return None;
+        }
+        let end = len + 1 + label.len();
+        buf.get_mut(len..end)?[0] = label.len() as u8;
+        buf[len + 1..end].copy_from_slice(label.as_bytes());
+        len = end;
+    }
+
+    let end = len + 5;
+    let question = buf.get_mut(len..end)?;
+    question[0] = 0;
+    question[1..3].copy_from_slice(&record_type.to_be_bytes());
+    question[3..5].copy_from_slice(&CLASS_IN.to_be_bytes());
+    Some(end)
+}
+
+/// Parse the response to the query with the given ID and check if `ip` is one of its addresses.
+/// Returns `None` if the message is not the response, or the response code if it is an error.
+/// A name that does not exist has no address, so it is not an error.
+pub fn parse_answers(
+    message: &[u8],
+    id: u16,
+    record_type: u16,
+    ip: IpAddr,
+) -> Option<Result<bool, u16>> {
+    let read_u16 = |i: usize| Some(u16::from_be_bytes([*message.get(i)?, *message.get(i + 1)?]));
+    let flags = read_u16(2)?;
+    if read_u16(0)? != id || flags & RESPONSE_FLAG == 0 {
+        return None;
+    }
+    match flags & 0x000f {
+        0 => (),
+        NAME_ERROR => return Some(Ok(false)),
+        rcode => return Some(Err(rcode)),
+    }
+
+    let questions = read_u16(4)?;
+    let answers = read_u16(6)?;
+    let mut i = HEADER_SIZE;
+    for _ in 0..questions {
+        i = skip_name(message, i)? + 4;
+    }
+    for _ in 0..answers {
+        i = skip_name(message, i)?;
+        let answer_type = read_u16(i)?;
+        let class = read_u16(i + 2)?;
+        let data_len = read_u16(i + 8)? as usize;
+        let data = message.get(i + 10..i + 10 + data_len)?;
+        i += 10 + data_len;
+
+        // The answers may start with the aliases of the name, which are skipped
+        if answer_type != record_type || class != CLASS_IN {
+            continue;
+        }
+        let answer = match data.len() {
+            4 => IpAddr::V4(Ipv4Addr::new(data[0], data[1], data[2], data[3])),
+            16 => {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(data);
+                IpAddr::V6(Ipv6Addr::from(octets))
+            }
+            _ => continue,
+        };
+        if answer == ip {
+            return Some(Ok(true));
+        }
+    }
+    Some(Ok(false))
+}
+
+/// Skip the name starting at index `i` of the message.
+/// Returns the index following the name, which ends with a null label or a pointer to another name.
+fn skip_name(message: &[u8], mut i: usize) -> Option<usize> {
+    loop {
+        let len = *message.get(i)? as usize;
+        match len {
+            0 => return Some(i + 1),
+            // The two high bits mark a pointer of 2 bytes
+            v if v & 0xc0 == 0xc0 => return Some(i + 2),
+            v => i += 1 + v,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ID: u16 = 0x1234;
+    const NAME: &str = "www.example.com";
+    /// A pointer to the name of the question, which starts right after the header.
+    const NAME_POINTER: [u8; 2] = [0xc0, 0x0c];
+    /// A pointer to "example.com" in the name of the question.
+    const DOMAIN_POINTER: [u8; 2] = [0xc0, 0x10];
+    /// The type of the alias records.
+    const TYPE_CNAME: u16 = 5;
+    const SERVER_FAILURE: u16 = 2;
+
+    /// Build the response to the query of `NAME` with the given answers: their name, type and data.
+    fn response(rcode: u16, record_type: u16, answers: &[(&[u8], u16, &[u8])]) -> Vec<u8> {
+        let mut message = vec![0u8; 512];
+        let len = write_query(&mut message, ID, NAME, record_type).unwrap();
+        message.truncate(len);
+        let flags = RESPONSE_FLAG | RECURSION_DESIRED | rcode;
+        message[2..4].copy_from_slice(&flags.to_be_bytes());
+        message[6..8].copy_from_slice(&(answers.len() as u16).to_be_bytes());
+        for (name, answer_type, data) in answers {
+            message.extend_from_slice(name);
+            message.extend_from_slice(&answer_type.to_be_bytes());
+            message.extend_from_slice(&CLASS_IN.to_be_bytes());
+            message.extend_from_slice(&300u32.to_be_bytes());
+            message.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            message.extend_from_slice(data);
+        }
+        message
+    }
+
+    fn ipv4(a: u8, b: u8, c: u8, d: u8) -> IpAddr {
+        IpAddr::V4(Ipv4Addr::new(a, b, c, d))
+    }
+
+    #[test]
+    fn writes_query() {
+        let mut buf = [0xffu8; 512];
+        let len = write_query(&mut buf, ID, "www.example.com.", TYPE_AAAA).unwrap();
+        let expected = b"\x12\x34\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\
+            \x03www\x07example\x03com\x00\x00\x1c\x00\x01";
+        assert_eq!(&buf[..len], expected);
+    }
+
+    #[test]
+    fn rejects_invalid_names() {
+        let mut buf = [0u8; 512];
+        assert_eq!(write_query(&mut buf, ID, "www..com", TYPE_A), None);
+        assert_eq!(write_query(&mut buf, ID, "", TYPE_A), None);
+        let label = "a".repeat(64);
+        assert_eq!(write_query(&mut buf, ID, &label, TYPE_A), None);
+        // The name does not fit in the buffer
+        assert_eq!(write_query(&mut buf[..20], ID, NAME, TYPE_A), None);
+    }
+
+    #[test]
+    fn finds_address() {
+        let message = response(0, TYPE_A, &[(&NAME_POINTER, TYPE_A, &[192, 0, 2, 1])]);
+        assert_eq!(
+            parse_answers(&message, ID, TYPE_A, ipv4(192, 0, 2, 1)),
+            Some(Ok(true))
+        );
+        assert_eq!(
+            parse_answers(&message, ID, TYPE_A, ipv4(192, 0, 2, 2)),
+            Some(Ok(false))
+        );
+    }
+
+    #[test]
+    fn finds_ipv6_address() {
+        let ip: Ipv6Addr = "2001:db8::1".parse().unwrap();
+        let message = response(0, TYPE_AAAA, &[(&NAME_POINTER, TYPE_AAAA, &ip.octets())]);
+        assert_eq!(
+            parse_answers(&message, ID, TYPE_AAAA, IpAddr::V6(ip)),
+            Some(Ok(true))
+        );
+    }
+
+    #[test]
+    fn skips_alias_before_address() {
+        // www.example.com is an alias of host.example.com, which has the address
+        let host = [b"\x04host".as_slice(), &DOMAIN_POINTER].concat();
+        let message = response(
+            0,
+            TYPE_A,
+            &[
+                (&NAME_POINTER, TYPE_CNAME, &host),
+                (&host, TYPE_A, &[192, 0, 2, 1]),
+            ],
+        );
+        assert_eq!(
+            parse_answers(&message, ID, TYPE_A, ipv4(192, 0, 2, 1)),
+            Some(Ok(true))
+        );
+    }
+
+    #[test]
+    fn skips_compressed_name() {
+        // A name ends with a null label or with a pointer after its first labels
+        let message = b"\x03www\xc0\x10\x07example\x03com\x00";
+        assert_eq!(skip_name(message, 0), Some(6));
+        assert_eq!(skip_name(message, 6), Some(19));
+        assert_eq!(skip_name(&message[..4], 0), None);
+    }
+
+    #[test]
+    fn missing_name_is_not_an_error() {
+        let message = response(NAME_ERROR, TYPE_A, &[]);
+        assert_eq!(
+            parse_answers(&message, ID, TYPE_A, ipv4(192, 0, 2, 1)),
+            Some(Ok(false))
+        );
+    }
+
+    #[test]
+    fn returns_error_code() {
+        let message = response(SERVER_FAILURE, TYPE_A, &[]);
+        assert_eq!(
+            parse_answers(&message, ID, TYPE_A, ipv4(192, 0, 2, 1)),
+            Some(Err(SERVER_FAILURE))
+        );
+    }
+
+    #[test]
+    fn ignores_other_messages() {
+        let message = response(0, TYPE_A, &[(&NAME_POINTER, TYPE_A, &[192, 0, 2, 1])]);
+        // The answer to another query
+        assert_eq!(
+            parse_answers(&message, ID + 1, TYPE_A, ipv4(192, 0, 2, 1)),
+            None
+        );
+        // A query
+        let mut query = message.clone();
+        query[2] &= !0x80;
+        assert_eq!(parse_answers(&query, ID, TYPE_A, ipv4(192, 0, 2, 1)), None);
+        assert_eq!(
+            parse_answers(&message[..3], ID, TYPE_A, ipv4(192, 0, 2, 1)),
+            None
+        );
+    }
+
+    #[test]
+    fn rejects_truncated_answer() {
+        let mut message = response(0, TYPE_A, &[(&NAME_POINTER, TYPE_A, &[192, 0, 2, 1])]);
+        // The data is longer than the rest of the message
+        let len = message.len();
+        message[len - 6..len - 4].copy_from_slice(&5u16.to_be_bytes());
+        assert_eq!(
+            parse_answers(&message, ID, TYPE_A, ipv4(192, 0, 2, 1)),
+            None
+        );
+        // The answer stops before its data length
+        let message = &message[..len - 6];
+        assert_eq!(parse_answers(message, ID, TYPE_A, ipv4(192, 0, 2, 1)), None);
+    }
+}
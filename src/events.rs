@@ -0,0 +1,82 @@
+use crate::http_server::HTTP_TASK_POOL_SIZE;
+use core::fmt::{self, Write};
+use embassy_net::IpAddress;
+use embassy_sync::{
+    blocking_mutex::raw::CriticalSectionRawMutex,
+    pubsub::{PubSubChannel, Subscriber},
+};
+
+/// The number of events kept for slow subscribers before they start missing events.
+const EVENT_QUEUE_SIZE: usize = 8;
+/// The maximum number of subscribers to the events.
+/// One HTTP server task is always kept free for the other requests.
+pub const MAX_SUBSCRIBERS: usize = HTTP_TASK_POOL_SIZE - 1;
+
+/// An event happening on the device.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    /// A WOL packet was sent to the given MAC address.
+    WolSent { mac_addr: [u8; 6] },
+    /// A switch pin was pulsed.
+    SwitchPulsed { pin: u8 },
+    /// The DNS updater found a new public IP address.
+    PublicIpChanged { ip: IpAddress },
+    /// The device connected to the wifi network.
+    WifiConnected,
+    /// The device lost its connection to the wifi network.
+    WifiDisconnected,
+}
+
+impl Event {
+    /// The name of the type of the event.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::WolSent { .. } => "wol",
+            Event::SwitchPulsed { .. } => "switch",
+            Event::PublicIpChanged { .. } => "public_ip",
+            Event::WifiConnected | Event::WifiDisconnected => "wifi",
+        }
+    }
+
+    /// Write the payload of the event as a JSON object.
+    pub fn write_json(&self, json: &mut impl Write) -> fmt::Result {
+        match self {
+            Event::WolSent { mac_addr } => {
+                let [a, b, c, d, e, f] = mac_addr;
+                write!(
+                    json,
+                    "{{\"mac_addr\":\"{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}\"}}",
+                    a, b, c, d, e, f
+                )
+            }
+            Event::SwitchPulsed { pin } => write!(json, "{{\"gpio\":{}}}", pin),
+            Event::PublicIpChanged { ip } => write!(json, "{{\"ip\":\"{}\"}}", ip),
+            Event::WifiConnected => write!(json, "{{\"connected\":true}}"),
+            Event::WifiDisconnected => write!(json, "{{\"connected\":false}}"),
+        }
+    }
+}
+
+/// A subscriber to the events of the device.
+pub type EventSubscriber =
+    Subscriber<'static, CriticalSectionRawMutex, Event, EVENT_QUEUE_SIZE, MAX_SUBSCRIBERS, 0>;
+
+pub static EVENTS: PubSubChannel<
+    CriticalSectionRawMutex,
+    Event,
+    EVENT_QUEUE_SIZE,
+    MAX_SUBSCRIBERS,
+    0,
+> = PubSubChannel::new();
+
+/// Publish an event to all the subscribers.
+/// If the queue is full, the oldest event is dropped.
+pub fn publish_event(event: Event) {
+    EVENTS.immediate_publisher().publish_immediate(event);
+}
+
+/// Subscribe to the events of the device.
+/// Returns `None` if there are already too many subscribers.
+pub fn subscribe() -> Option<EventSubscriber> {
+    EVENTS.subscriber().ok()
+}
@@ -0,0 +1,84 @@
+use embedded_storage::{ReadStorage, Storage};
+use esp_storage::FlashStorage;
+
+/// The offset in flash of the partition table written by the flasher.
+const PARTITION_TABLE_OFFSET: u32 = 0x8000;
+/// The maximum number of entries of the partition table.
+const MAX_PARTITIONS: u32 = 95;
+/// The size of an entry of the partition table.
+const PARTITION_ENTRY_SIZE: usize = 32;
+/// The first bytes of the entries of the partition table.
+/// The table ends with an entry starting with other bytes, like its checksum or erased flash.
+const PARTITION_MAGIC: [u8; 2] = [0xAA, 0x50];
+/// The type of the data partitions.
+const PARTITION_TYPE_DATA: u8 = 0x01;
+
+/// A data partition of the flash.
+#[derive(Debug, Clone, Copy)]
+pub struct Partition {
+    offset: u32,
+    size: u32,
+}
+
+impl Partition {
+    /// Find the data partition with the given label in the partition table.
+    pub fn find(label: &str) -> Option<Self> {
+        let mut storage = FlashStorage::new();
+        let mut entry = [0u8; PARTITION_ENTRY_SIZE];
+        for i in 0..MAX_PARTITIONS {
+            let offset = PARTITION_TABLE_OFFSET + i * PARTITION_ENTRY_SIZE as u32;
+            if let Err(e) = storage.read(offset, &mut entry) {
+                log::error!("SYS | Error reading partition table: {:?}", e);
+                return None;
+            }
+            if entry[..2] != PARTITION_MAGIC {
+                break;
+            }
+
+            // The label is padded with null bytes
+            let name = &entry[12..28];
+            let name = &name[..name.iter().position(|&v| v == 0).unwrap_or(name.len())];
+            if entry[2] == PARTITION_TYPE_DATA && name == label.as_bytes() {
+                return Some(Self {
+                    offset: u32::from_le_bytes([entry[4], entry[5], entry[6], entry[7]]),
+                    size: u32::from_le_bytes([entry[8], entry[9], entry[10], entry[11]]),
+                });
+            }
+        }
+
+        log::error!("SYS | No data partition named {} in partition table", label);
+        None
+    }
+
+    /// Read the bytes at `offset` from the start of the partition.
+    pub fn read(&self, offset: u32, buf: &mut [u8]) -> Result<(), ()> {
+        let start = self.start(offset, buf.len())?;
+        FlashStorage::new().read(start, buf).map_err(|e| {
+            log::error!("SYS | Error reading flash at {:#x}: {:?}", start, e);
+        })
+    }
+
+    /// Write the bytes at `offset` from the start of the partition.
+    /// The sectors written to are erased, but the bytes around the written ones are kept.
+    pub fn write(&self, offset: u32, buf: &[u8]) -> Result<(), ()> {
+        let start = self.start(offset, buf.len())?;
+        FlashStorage::new().write(start, buf).map_err(|e| {
+            log::error!("SYS | Error writing flash at {:#x}: {:?}", start, e);
+        })
+    }
+
+    /// Get the offset in flash of the bytes at `offset` from the start of the partition,
+    /// checking that `len` bytes fit in the partition.
+    fn start(&self, offset: u32, len: usize) -> Result<u32, ()> {
+        let end = offset.checked_add(len as u32).ok_or(())?;
+        if end > self.size {
+            log::error!(
+                "SYS | Access of {} bytes at {:#x} is out of the partition",
+                len,
+                offset
+            );
+            return Err(());
+        }
+        Ok(self.offset + offset)
+    }
+}
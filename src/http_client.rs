@@ -0,0 +1,223 @@
+mod response;
+
+use crate::utils::{abort_connection, get_global_ipv6, write_tcp_buf};
+use embassy_net::{IpAddress, IpEndpoint, Stack, dns::DnsQueryType, tcp::TcpSocket};
+use embassy_time::{Duration, Timer};
+pub use response::Response;
+use response::response_length;
+#[cfg(feature = "tls")]
+use {
+    crate::tls::{self, ServerName, TcpStream, Verification},
+    embassy_time::with_timeout,
+    mbedtls_rs::Session,
+};
+
+/// The buffer size for the TCP socket.
+const TCP_BUFFER_SIZE: usize = 1024;
+/// The time in seconds after which an unresponsive connection is dropped.
+const SOCKET_TIMEOUT: u64 = 10;
+
+/// How the connection to the server is made.
+pub enum Transport {
+    /// Plain text HTTP.
+    Plain,
+    /// HTTPS, with the certificate of the server verified as given.
+    #[cfg(feature = "tls")]
+    Tls(Verification<'static>),
+}
+
+/// A connection to an HTTP server.
+enum Connection<'a, 's> {
+    Plain(&'a mut TcpSocket<'s>),
+    #[cfg(feature = "tls")]
+    Tls(Session<'a, TcpStream<'a, 's>>),
+}
+
+/// Sends an HTTP/1.1 request to the host and reads its response into the buffer.
+/// The request should ask the server to close the connection, as it is not reused.
+/// The log messages are prefixed with `prefix`.
+pub async fn send_request<'b>(
+    stack: Stack<'_>,
+    host: &str,
+    port: u16,
+    transport: &Transport,
+    request: &[u8],
+    response_buf: &'b mut [u8],
+    prefix: &str,
+) -> Result<Response<'b>, ()> {
+    let remote_endpoint = IpEndpoint::new(resolve(stack, host, prefix).await?, port);
+
+    // Setup TCP socket
+    let mut rx_buffer = [0; TCP_BUFFER_SIZE];
+    let mut tx_buffer = [0; TCP_BUFFER_SIZE];
+    let mut socket = TcpSocket::new(stack, &mut rx_buffer, &mut tx_buffer);
+    socket.set_timeout(Some(Duration::from_secs(SOCKET_TIMEOUT)));
+
+    // Connect to the remote endpoint
+    log::info!("{} | Connecting to {}...", prefix, host);
+    if let Err(e) = socket.connect(remote_endpoint).await {
+        abort_connection(&mut socket).await;
+        log::error!("{} | Error connecting to {}: {:?}", prefix, host, e);
+        return Err(());
+    }
+    log::info!("{} | Connected to {}!", prefix, host);
+
+    let len = match exchange(&mut socket, host, transport, request, response_buf, prefix).await {
+        Ok(v) => v,
+        Err(_) => {
+            abort_connection(&mut socket).await;
+            return Err(());
+        }
+    };
+
+    socket.close();
+    Timer::after(Duration::from_millis(500)).await;
+    abort_connection(&mut socket).await;
+
+    Response::parse(&mut response_buf[..len])
+        .map_err(|_| log::error!("{} | Invalid HTTP response from {}", prefix, host))
+}
+
+/// Sends the request on the connected socket and reads the response into the buffer.
+/// Returns the length of the response.
+async fn exchange(
+    socket: &mut TcpSocket<'_>,
+    host: &str,
+    transport: &Transport,
+    request: &[u8],
+    response_buf: &mut [u8],
+    prefix: &str,
+) -> Result<usize, ()> {
+    #[cfg(feature = "tls")]
+    let server_name;
+    let mut connection = match transport {
+        Transport::Plain => Connection::Plain(socket),
+        #[cfg(feature = "tls")]
+        Transport::Tls(verification) => {
+            server_name = ServerName::new(host)
+                .map_err(|_| log::error!("{} | Invalid host name for TLS: {}", prefix, host))?;
+            Connection::Tls(tls::connect(socket, &server_name, verification, prefix).await?)
+        }
+    };
+
+    log::info!("{} | Writing HTTP request to {}...", prefix, host);
+    if connection.write_buf(request).await.is_err() {
+        log::error!("{} | Error writing request to {}", prefix, host);
+        return Err(());
+    }
+
+    // The response may be split across several segments, so read until it is complete
+    let mut len = 0;
+    let mut closed = false;
+    let len = loop {
+        if let Some(v) = response_length(&response_buf[..len], closed) {
+            break v;
+        }
+        if closed {
+            log::error!("{} | Response from {} was cut", prefix, host);
+            return Err(());
+        }
+        if len == response_buf.len() {
+            log::error!("{} | Response from {} does not fit in buffer", prefix, host);
+            return Err(());
+        }
+
+        match connection.read(&mut response_buf[len..]).await {
+            Ok(0) => closed = true,
+            Ok(n) => len += n,
+            // Some servers close the connection without notifying the client
+            Err(_) if len > 0 => closed = true,
+            Err(_) => {
+                log::error!("{} | Error reading response from {}", prefix, host);
+                return Err(());
+            }
+        }
+    };
+
+    if connection.close().await.is_err() {
+        log::warn!("{} | Connection to {} took too long to close", prefix, host);
+    }
+    Ok(len)
+}
+
+impl Connection<'_, '_> {
+    /// Read from the connection into the buffer.
+    /// Returns the number of bytes read, which is 0 if the connection was closed.
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, ()> {
+        match self {
+            Connection::Plain(socket) => socket.read(buf).await.map_err(|_| ()),
+            #[cfg(feature = "tls")]
+            Connection::Tls(session) => session.read(buf).await.map_err(|_| ()),
+        }
+    }
+
+    /// Writes a buffer to the connection, then flushes it.
+    async fn write_buf(&mut self, buf: &[u8]) -> Result<(), ()> {
+        match self {
+            Connection::Plain(socket) => write_tcp_buf(socket, buf).await,
+            #[cfg(feature = "tls")]
+            Connection::Tls(session) => {
+                let mut buf = buf;
+                while !buf.is_empty() {
+                    match session.write(buf).await {
+                        Ok(0) | Err(_) => return Err(()),
+                        Ok(n) => buf = &buf[n..],
+                    }
+                }
+                session.flush().await.map_err(|_| ())
+            }
+        }
+    }
+
+    /// Notify the server that the connection is closed, which is only needed by TLS connections.
+    /// Returns an error if the server took too long to answer.
+    async fn close(self) -> Result<(), ()> {
+        match self {
+            Connection::Plain(_) => Ok(()),
+            #[cfg(feature = "tls")]
+            Connection::Tls(mut session) => {
+                with_timeout(Duration::from_millis(500), session.close())
+                    .await
+                    .map_err(|_| ())?
+                    .map_err(|_| ())
+            }
+        }
+    }
+}
+
+/// Queries the DNS server for the IP address of the host.
+/// IPv4 is preferred, and IPv6 is used when the host has no IPv4 address and the device has a global IPv6 address.
+async fn resolve(stack: Stack<'_>, host: &str, prefix: &str) -> Result<IpAddress, ()> {
+    log::info!("{} | Resolving IP for {}...", prefix, host);
+    let mut ip = query_address(stack, host, DnsQueryType::A, prefix).await;
+    if ip.is_none() && get_global_ipv6(stack).is_some() {
+        ip = query_address(stack, host, DnsQueryType::Aaaa, prefix).await;
+    }
+
+    match ip {
+        Some(v) => {
+            log::info!("{} | Found IP for {}: {}", prefix, host, v);
+            Ok(v)
+        }
+        None => {
+            log::error!("{} | No IP found for {}", prefix, host);
+            Err(())
+        }
+    }
+}
+
+/// Queries the DNS server for the first address of the given type of the host.
+async fn query_address(
+    stack: Stack<'_>,
+    host: &str,
+    query_type: DnsQueryType,
+    prefix: &str,
+) -> Option<IpAddress> {
+    match stack.dns_query(host, query_type).await {
+        Ok(v) => v.first().copied(),
+        Err(e) => {
+            log::error!("{} | Error querying DNS server: {:?}", prefix, e);
+            None
+        }
+    }
+}
@@ -0,0 +1,300 @@
+/// A response of an HTTP/1.1 server, with its body decoded.
+pub struct Response<'a> {
+    /// The status code of the response.
+    pub status: u16,
+    /// The header lines of the response, without the status line.
+    headers: &'a str,
+    /// The body of the response.
+    body: &'a [u8],
+}
+
+impl<'a> Response<'a> {
+    /// Parse a complete response, as delimited by [`response_length`].
+    /// A chunked body is decoded in place.
+    pub fn parse(buf: &'a mut [u8]) -> Result<Self, ()> {
+        let head_end = find(buf, b"\r\n\r\n").ok_or(())? + 4;
+        let chunked = core::str::from_utf8(&buf[..head_end])
+            .map(is_chunked)
+            .map_err(|_| ())?;
+        let body_len = match chunked {
+            true => decode_chunks(&mut buf[head_end..]).ok_or(())?,
+            false => buf.len() - head_end,
+        };
+
+        let buf: &'a [u8] = buf;
+        let (head, body) = buf.split_at(head_end);
+        let head = core::str::from_utf8(head).map_err(|_| ())?;
+        let (status_line, headers) = head.split_once("\r\n").ok_or(())?;
+        Ok(Self {
+            status: parse_status(status_line).ok_or(())?,
+            headers,
+            body: &body[..body_len],
+        })
+    }
+
+    /// Check if the status code is a success (2xx).
+    pub fn is_success(&self) -> bool {
+        (200..=299).contains(&self.status)
+    }
+
+    /// Get the value of a header, whose name is case insensitive.
+    pub fn header(&self, name: &str) -> Option<&'a str> {
+        find_header(self.headers, name)
+    }
+
+    /// Get the body as text, if it is valid UTF8.
+    pub fn text(&self) -> Option<&'a str> {
+        core::str::from_utf8(self.body).ok()
+    }
+
+    /// Get the number of seconds of the `Retry-After` header.
+    /// The HTTP date format is not supported, as the device does not know the current date.
+    pub fn retry_after(&self) -> Option<u64> {
+        self.header("Retry-After")?.parse().ok()
+    }
+}
+
+/// Get the length of the response at the start of the buffer once it is complete.
+/// The end of the response is given by its `Content-Length` or its last chunk,
+/// or by the end of the connection when it has neither, in which case `closed` must be set.
+pub fn response_length(buf: &[u8], closed: bool) -> Option<usize> {
+    let head_end = find(buf, b"\r\n\r\n")? + 4;
+    let head = core::str::from_utf8(&buf[..head_end]).ok()?;
+    let status = parse_status(head.lines().next()?)?;
+
+    // These responses never have a body
+    if matches!(status, 100..=199 | 204 | 304) {
+        return Some(head_end);
+    }
+    if is_chunked(head) {
+        return chunked_length(&buf[head_end..]).map(|v| head_end + v);
+    }
+    match find_header(head, "Content-Length").map(|v| v.parse::<usize>()) {
+        Some(Ok(v)) => {
+            // A length that cannot be buffered is rejected
+            let end = head_end.checked_add(v)?;
+            (buf.len() >= end).then_some(end)
+        }
+        // An invalid length cannot be trusted, so wait until the end of the connection
+        Some(Err(_)) | None => closed.then_some(buf.len()),
+    }
+}
+
+/// Parse the status code from the status line of an HTTP response, like `HTTP/1.1 200 OK`.
+fn parse_status(status_line: &str) -> Option<u16> {
+    let mut parts = status_line.split(' ');
+    if !parts.next()?.starts_with("HTTP/") {
+        return None;
+    }
+    parts.next()?.parse().ok()
+}
+
+/// Find the value of a header in the header lines, skipping the status line if present.
+fn find_header<'a>(head: &'a str, name: &str) -> Option<&'a str> {
+    head.lines()
+        .filter_map(|line| line.split_once(':'))
+        .find(|(v, _)| v.trim().eq_ignore_ascii_case(name))
+        .map(|(_, v)| v.trim())
+}
+
+/// Check if the body is sent in chunks.
+fn is_chunked(head: &str) -> bool {
+    find_header(head, "Transfer-Encoding").is_some_and(|v| {
+        v.rsplit(',')
+            .next()
+            .is_some_and(|v| v.trim().eq_ignore_ascii_case("chunked"))
+    })
+}
+
+/// A chunk of a chunked body.
+struct Chunk {
+    /// The index of the data of the chunk.
+    start: usize,
+    /// The length of the data of the chunk, which is 0 for the last chunk.
+    len: usize,
+    /// The index following the chunk, including the trailer section of the last chunk.
+    end: usize,
+}
+
+/// Parse the chunk at the start of the body.
+/// Returns `None` if the chunk is incomplete or invalid.
+fn next_chunk(body: &[u8]) -> Option<Chunk> {
+    let line_end = find(body, b"\r\n")?;
+    let size = core::str::from_utf8(&body[..line_end]).ok()?;
+    // Chunk extensions after the size are ignored
+    let size = size.split(';').next()?.trim();
+    let len = usize::from_str_radix(size, 16).ok()?;
+    let start = line_end + 2;
+
+    let end = match len {
+        // The last chunk is followed by optional trailer fields and an empty line
+        0 if body[start..].starts_with(b"\r\n") => start + 2,
+        0 => start + find(&body[start..], b"\r\n\r\n")? + 4,
+        _ => {
+            let end = start.checked_add(len)?.checked_add(2)?;
+            if body.get(end - 2..end)? != b"\r\n" {
+                return None;
+            }
+            end
+        }
+    };
+    Some(Chunk { start, len, end })
+}
+
+/// Get the length of a chunked body once its last chunk is received.
+fn chunked_length(body: &[u8]) -> Option<usize> {
+    let mut read = 0;
+    loop {
+        let chunk = next_chunk(&body[read..])?;
+        read += chunk.end;
+        if chunk.len == 0 {
+            return Some(read);
+        }
+    }
+}
+
+/// Decode a complete chunked body in place.
+/// Returns the length of the decoded body.
+fn decode_chunks(body: &mut [u8]) -> Option<usize> {
+    let mut read = 0;
+    let mut written = 0;
+    loop {
+        let chunk = next_chunk(&body[read..])?;
+        if chunk.len == 0 {
+            return Some(written);
+        }
+        let start = read + chunk.start;
+        body.copy_within(start..start + chunk.len, written);
+        written += chunk.len;
+        read += chunk.end;
+    }
+}
+
+/// Find the first occurrence of the needle in the haystack.
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|v| v == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHUNKED: &[u8] = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5;ext=1\r\nhello\r\n7\r\n, world\r\n0\r\n\r\n";
+
+    #[test]
+    fn waits_for_content_length() {
+        let buf = b"HTTP/1.1 200 OK\r\nContent-Length: 5\r\n\r\nhello";
+        for len in 0..buf.len() {
+            assert_eq!(response_length(&buf[..len], false), None, "{} bytes", len);
+        }
+        assert_eq!(response_length(buf, false), Some(buf.len()));
+
+        // The bytes after the body are not part of the response
+        let mut extra = buf.to_vec();
+        extra.extend_from_slice(b"HTTP/1.1");
+        assert_eq!(response_length(&extra, true), Some(buf.len()));
+    }
+
+    #[test]
+    fn rejects_overflowing_content_length() {
+        let buf = b"HTTP/1.1 200 OK\r\nContent-Length: 18446744073709551615\r\n\r\nhello";
+        assert_eq!(response_length(buf, false), None);
+        assert_eq!(response_length(buf, true), None);
+    }
+
+    #[test]
+    fn waits_for_close_without_length() {
+        let buf = b"HTTP/1.0 200 OK\r\nContent-Type: text/plain\r\n\r\nhello";
+        assert_eq!(response_length(buf, false), None);
+        assert_eq!(response_length(buf, true), Some(buf.len()));
+
+        // An invalid length cannot be trusted either
+        let buf = b"HTTP/1.1 200 OK\r\nContent-Length: five\r\n\r\nhello";
+        assert_eq!(response_length(buf, false), None);
+        assert_eq!(response_length(buf, true), Some(buf.len()));
+    }
+
+    #[test]
+    fn ignores_body_of_bodiless_statuses() {
+        for status in ["100 Continue", "204 No Content", "304 Not Modified"] {
+            let buf = format!("HTTP/1.1 {}\r\nContent-Length: 5\r\n\r\n", status);
+            assert_eq!(
+                response_length(buf.as_bytes(), false),
+                Some(buf.len()),
+                "{}",
+                status
+            );
+        }
+    }
+
+    #[test]
+    fn waits_for_last_chunk() {
+        for len in 0..CHUNKED.len() {
+            assert_eq!(
+                response_length(&CHUNKED[..len], true),
+                None,
+                "{} bytes",
+                len
+            );
+        }
+        assert_eq!(response_length(CHUNKED, false), Some(CHUNKED.len()));
+
+        // Chunked encoding takes precedence over the length
+        let buf = b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nTransfer-Encoding: gzip, chunked\r\n\r\n0\r\n\r\n";
+        assert_eq!(response_length(buf, false), Some(buf.len()));
+    }
+
+    #[test]
+    fn includes_trailers_of_last_chunk() {
+        let buf = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n2\r\nok\r\n0\r\nX-Trailer: a\r\n\r\n";
+        assert_eq!(response_length(&buf[..buf.len() - 2], false), None);
+        assert_eq!(response_length(buf, false), Some(buf.len()));
+    }
+
+    #[test]
+    fn rejects_invalid_chunks() {
+        for body in [
+            &b"g\r\nhello\r\n0\r\n\r\n"[..],
+            b"5\r\nhelloXX0\r\n\r\n",
+            b"ffffffffffffffff\r\nhello\r\n0\r\n\r\n",
+        ] {
+            let mut buf = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n".to_vec();
+            buf.extend_from_slice(body);
+            assert_eq!(response_length(&buf, true), None, "{:?}", body);
+            assert!(Response::parse(&mut buf).is_err(), "{:?}", body);
+        }
+    }
+
+    #[test]
+    fn decodes_chunked_body() {
+        let mut buf = CHUNKED.to_vec();
+        let response = Response::parse(&mut buf).unwrap();
+        assert_eq!(response.status, 200);
+        assert!(response.is_success());
+        assert_eq!(response.text(), Some("hello, world"));
+    }
+
+    #[test]
+    fn parses_status_and_headers() {
+        let mut buf = b"HTTP/1.1 503 Service Unavailable\r\nretry-after: 120\r\nContent-Length: 4\r\n\r\nbusy".to_vec();
+        let response = Response::parse(&mut buf).unwrap();
+        assert_eq!(response.status, 503);
+        assert!(!response.is_success());
+        assert_eq!(response.header("Content-Length"), Some("4"));
+        assert_eq!(response.header("Missing"), None);
+        assert_eq!(response.retry_after(), Some(120));
+        assert_eq!(response.text(), Some("busy"));
+    }
+
+    #[test]
+    fn rejects_invalid_status_line() {
+        for buf in [
+            &b"HTTP/1.1\r\n\r\n"[..],
+            b"ICY 200 OK\r\n\r\n",
+            b"HTTP/1.1 OK\r\n\r\n",
+        ] {
+            assert_eq!(response_length(buf, true), None, "{:?}", buf);
+            assert!(Response::parse(&mut buf.to_vec()).is_err(), "{:?}", buf);
+        }
+    }
+}
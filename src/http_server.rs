@@ -1,43 +1,133 @@
+mod api;
+mod auth;
+mod connection;
+mod dashboard;
+mod events_utils;
+mod form;
 mod html_responses;
+#[cfg(feature = "https")]
+mod https;
+mod metrics_utils;
+mod request;
+mod response;
+mod router;
+mod status;
 mod switch_utils;
+mod websocket_utils;
 mod wol_utils;
 
-use crate::utils::{abort_connection, wait_for_connection, write_tcp_buf};
+use crate::{
+    metrics::record_http_request,
+    utils::{abort_connection, wait_for_connection},
+};
+use connection::Connection;
+use core::fmt::Write;
 
-use embassy_net::{IpListenEndpoint, Stack, tcp::TcpSocket};
+use embassy_net::{IpAddress, IpListenEndpoint, Stack, tcp::TcpSocket};
 use embassy_time::{Duration, Timer};
 use esp_backtrace as _;
-use heapless::FnvIndexMap;
-use html_responses::{HTML_HEADER, HTML_MENU, HTML_TAIL};
-use switch_utils::switch_command;
-use wol_utils::wol_command;
-
-/// The HTTP headers for the response.
-const HTTP_HEADERS: &[u8] =
-    b"HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nConnection: close\r\n\r\n";
+use heapless::String;
+use html_responses::{
+    HTML_HEADER, HTML_MENU_HEAD, HTML_MENU_ITEM_END, HTML_MENU_ITEM_MIDDLE, HTML_MENU_ITEM_START,
+    HTML_MENU_TAIL, HTML_TAIL,
+};
+use request::{ParseError, REQUEST_BUFFER_SIZE, Request};
+use response::{ContentType, Response};
+use status::StatusCode;
+
+#[cfg(feature = "https")]
+pub use https::redirect_enabled as https_redirect_enabled;
+
+/// The HTTP headers ending every response, following the status line and the other headers.
+const HTTP_HEADERS: &[u8] = b"Connection: close\r\n\r\n";
+/// The HTTP headers ending the response accepting a WebSocket connection.
+const WEBSOCKET_HEADERS: &[u8] = b"Connection: Upgrade\r\nUpgrade: websocket\r\n\r\n";
 /// The port on which the device will listen for HTTP requests.
 const HTTP_LISTEN_PORT: &str = env!("HTTP_LISTEN_PORT");
 /// The fallback port on which the device will listen for HTTP requests.
 const HTTP_LISTEN_PORT_FALLBACK: u16 = 8080;
+/// The maximum number of HTTP connections handled in parallel.
+const HTTP_MAX_CONNECTIONS: &str = env!("HTTP_MAX_CONNECTIONS");
+/// The number of HTTP server tasks that can be spawned.
+/// Each task handles one connection at a time.
+#[cfg(not(feature = "https"))]
+pub const HTTP_TASK_POOL_SIZE: usize = 4;
+/// The number of HTTPS server tasks that can be spawned.
+/// Each task needs a TLS session, and one of the sessions is kept for the DNS updater.
+#[cfg(feature = "https")]
+pub const HTTP_TASK_POOL_SIZE: usize = crate::tls::MAX_TLS_SESSIONS - 1;
 /// The buffer size for the TCP socket.
-/// It should be big enough to contain the HTTP requests and responses.
+/// Requests must fit in `REQUEST_BUFFER_SIZE`, while responses are streamed through it.
+#[cfg(not(feature = "https"))]
 const TCP_BUFFER_SIZE: usize = 4096;
-/// The enable flag for the WOL feature.
-const WOL_ENABLE: &str = env!("WOL_ENABLE");
-/// The enable flag for the Switch feature.
-const SWITCH_ENABLE: &str = env!("SWITCH_ENABLE");
+/// The buffer size for the TCP socket of the HTTPS server tasks.
+/// The TLS session buffers whole records, so smaller buffers leave room in the memory for its sessions.
+#[cfg(feature = "https")]
+const TCP_BUFFER_SIZE: usize = 1024;
+/// The buffer size for the TCP socket of the HTTPS redirect listener, which only sends short responses.
+#[cfg(feature = "https")]
+const REDIRECT_BUFFER_SIZE: usize = 1024;
 
-/// The embassy task that handles the HTTP server.
-#[embassy_executor::task]
-pub async fn http_server_task(stack: Stack<'static>) {
-    let listening_port = match HTTP_LISTEN_PORT.parse::<u16>() {
+/// The errors that can occur while running a command.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandError {
+    /// The arguments of the command are invalid.
+    InvalidInput,
+    /// The command could not be executed.
+    Failed,
+}
+
+/// Get the number of HTTP server tasks to spawn from the connection limit.
+/// Falls back to the size of the task pool if the limit cannot be parsed,
+/// and is capped to the size of the task pool.
+pub fn get_http_task_count() -> usize {
+    let count = match HTTP_MAX_CONNECTIONS.parse::<usize>() {
+        Ok(v) if v > 0 => v,
+        _ => {
+            log::error!(
+                "HTTP | Invalid HTTP_MAX_CONNECTIONS: {}. Using {} connections",
+                HTTP_MAX_CONNECTIONS,
+                HTTP_TASK_POOL_SIZE
+            );
+            HTTP_TASK_POOL_SIZE
+        }
+    };
+
+    if count > HTTP_TASK_POOL_SIZE {
+        log::warn!(
+            "HTTP | HTTP_MAX_CONNECTIONS is capped to {} connections",
+            HTTP_TASK_POOL_SIZE
+        );
+        return HTTP_TASK_POOL_SIZE;
+    }
+    count
+}
+
+/// Get the port on which the device listens for plain HTTP requests.
+fn get_http_listen_port() -> u16 {
+    match HTTP_LISTEN_PORT.parse::<u16>() {
         Ok(v) => v,
         Err(e) => {
             log::error!("HTTP | Could not parse port number: {:?}", e);
             log::error!("HTTP | Using default port {}", HTTP_LISTEN_PORT_FALLBACK);
             HTTP_LISTEN_PORT_FALLBACK
         }
-    };
+    }
+}
+
+/// The embassy task that handles the HTTP server.
+/// Several instances can run in parallel, each one accepting connections on the same port.
+/// With the `https` feature, the connections are encrypted with TLS.
+#[embassy_executor::task(pool_size = HTTP_TASK_POOL_SIZE)]
+pub async fn http_server_task(stack: Stack<'static>, id: usize) {
+    #[cfg(not(feature = "https"))]
+    let listening_port = get_http_listen_port();
+    #[cfg(feature = "https")]
+    let listening_port = https::get_https_listen_port();
+
+    if auth::is_enabled() && !auth::basic_enabled() && !auth::bearer_enabled() {
+        log::error!("HTTP | Authentication is enabled without credentials, denying all requests");
+    }
 
     let listening_endpoint = IpListenEndpoint {
         addr: None,
@@ -47,7 +137,6 @@ pub async fn http_server_task(stack: Stack<'static>) {
     // Setup TCP socket
     let mut rx_buffer = [0; TCP_BUFFER_SIZE];
     let mut tx_buffer = [0; TCP_BUFFER_SIZE];
-    let response_buffer = &mut [0; TCP_BUFFER_SIZE];
     let mut socket = TcpSocket::new(stack, &mut rx_buffer, &mut tx_buffer);
     socket.set_timeout(Some(embassy_time::Duration::from_secs(10)));
 
@@ -56,7 +145,8 @@ pub async fn http_server_task(stack: Stack<'static>) {
 
         // Wait for incoming connection
         log::info!(
-            "HTTP | Waiting for connection on port {}...",
+            "HTTP | Task {} waiting for connection on port {}...",
+            id,
             listening_endpoint.port
         );
         if let Err(e) = socket.accept(listening_endpoint).await {
@@ -73,137 +163,309 @@ pub async fn http_server_task(stack: Stack<'static>) {
                 continue;
             }
         };
-        log::info!("HTTP | Accepted connection to {}", remote_endpoint_addr);
-
-        let mut read_buffer = [0u8; TCP_BUFFER_SIZE];
-        match socket.read(&mut read_buffer).await {
-            Ok(0) => log::info!("HTTP | Connection closed"),
-            Ok(len) => {
-                // Parse the query as UTF8 and print it
-                let query = match core::str::from_utf8(&read_buffer[..len]) {
-                    Ok(v) => v,
-                    Err(e) => {
-                        log::error!("HTTP | Query was not UTF8: {:?}", e);
-                        abort_connection(&mut socket).await;
-                        continue;
-                    }
-                };
-
-                let html = match handle_http_query(stack, query).await {
-                    Ok(v) => v,
-                    Err(_) => html_responses::ERROR,
-                };
-
-                let response_len = match generate_http_response(html, response_buffer) {
-                    Ok(v) => v,
-                    Err(e) => {
-                        log::error!("HTTP | Error generating response: {:?}", e);
-                        abort_connection(&mut socket).await;
-                        continue;
-                    }
-                };
-
-                if (write_tcp_buf(&mut socket, &response_buffer[..response_len]).await).is_err() {
-                    log::error!("HTTP | Error writing response");
-                    abort_connection(&mut socket).await;
-                    continue;
-                }
-            }
-            Err(e) => log::error!("HTTP | Error reading response: {:?}", e),
-        };
+        log::info!(
+            "HTTP | Task {} accepted connection to {}",
+            id,
+            remote_endpoint_addr
+        );
+
+        if handle_connection(stack, &mut socket, remote_endpoint_addr)
+            .await
+            .is_err()
+        {
+            abort_connection(&mut socket).await;
+            continue;
+        }
 
-        log::info!("HTTP | Closing connection to {}", remote_endpoint_addr);
-        socket.close();
         Timer::after(Duration::from_millis(50)).await;
         abort_connection(&mut socket).await;
     }
 }
 
-/// Handle the HTTP query and return the appropriate response.
-async fn handle_http_query(stack: Stack<'_>, query: &str) -> Result<&'static [u8], ()> {
-    // Parse the command and arguments
-    let full_command = query.split_whitespace().nth(1).unwrap_or("/");
-    let (command, full_args) = full_command.split_once('?').unwrap_or((full_command, ""));
-
-    log::info!("HTTP | Command: {}", full_command);
-
-    // Collect args in a hashmap
-    let mut args = FnvIndexMap::<_, _, 4>::new();
-    if !full_args.is_empty() {
-        full_args.split('&').for_each(|v| {
-            let (key, value) = v.split_once('=').unwrap_or((v, ""));
-            if let Err(e) = args.insert(key, value) {
-                log::warn!("HTTP | Query sepecified too many arguments: {:?}", e);
+/// The embassy task redirecting the plain HTTP requests to the HTTPS server.
+#[cfg(feature = "https")]
+#[embassy_executor::task]
+pub async fn http_redirect_task(stack: Stack<'static>) {
+    let https_port = https::get_https_listen_port();
+    let listening_endpoint = IpListenEndpoint {
+        addr: None,
+        port: get_http_listen_port(),
+    };
+
+    // Setup TCP socket
+    let mut rx_buffer = [0; REDIRECT_BUFFER_SIZE];
+    let mut tx_buffer = [0; REDIRECT_BUFFER_SIZE];
+    let mut socket = TcpSocket::new(stack, &mut rx_buffer, &mut tx_buffer);
+    socket.set_timeout(Some(embassy_time::Duration::from_secs(10)));
+
+    loop {
+        wait_for_connection(stack).await;
+
+        log::info!(
+            "HTTP | Redirecting to HTTPS from port {}...",
+            listening_endpoint.port
+        );
+        if let Err(e) = socket.accept(listening_endpoint).await {
+            log::error!("HTTP | Error accepting connection: {:?}", e);
+            abort_connection(&mut socket).await;
+            continue;
+        };
+
+        // The address of the device is used when the client does not send its host
+        let mut local_addr = String::<48>::new();
+        if let Some(v) = socket.local_endpoint() {
+            let _ = write!(local_addr, "{}", v.addr);
+        }
+
+        let mut connection = Connection::Plain(&mut socket);
+        let mut read_buffer = [0u8; REQUEST_BUFFER_SIZE];
+        let response = match read_request(&mut connection, &mut read_buffer).await {
+            Ok(Some(len)) => match request::parse(&read_buffer[..len]) {
+                Ok(Some(request)) => https::redirect_response(&request, &local_addr, https_port),
+                Ok(None) => Response::html(StatusCode::BadRequest, html_responses::BAD_REQUEST),
+                Err(e) => Response::html(e.status(), html_responses::BAD_REQUEST),
+            },
+            Ok(None) | Err(ReadError::Connection) => {
+                drop(connection);
+                abort_connection(&mut socket).await;
+                continue;
             }
-        });
+            Err(ReadError::Invalid(e)) => Response::html(e.status(), html_responses::BAD_REQUEST),
+        };
+
+        if write_response(&mut connection, &response).await.is_err() {
+            log::error!("HTTP | Error writing response");
+            drop(connection);
+            abort_connection(&mut socket).await;
+            continue;
+        }
+
+        connection.close().await;
+        Timer::after(Duration::from_millis(50)).await;
+        abort_connection(&mut socket).await;
     }
+}
+
+/// Open a connection on an accepted socket, read a request, answer it,
+/// and stream the events if it opened a stream, then close the connection.
+/// Returns an error if the connection must be aborted instead of being closed.
+async fn handle_connection(
+    stack: Stack<'_>,
+    socket: &mut TcpSocket<'_>,
+    remote_endpoint_addr: IpAddress,
+) -> Result<(), ()> {
+    #[cfg(not(feature = "https"))]
+    let mut connection = Connection::Plain(socket);
+    #[cfg(feature = "https")]
+    let mut connection = https::accept(socket).await?;
 
-    match command {
-        "/wol" => {
-            if WOL_ENABLE != "true" && WOL_ENABLE != "1" {
-                return Ok(html_responses::NOT_ENABLED);
+    let mut read_buffer = [0u8; REQUEST_BUFFER_SIZE];
+    let (route, mut response) = match read_request(&mut connection, &mut read_buffer).await {
+        Ok(Some(len)) => match request::parse(&read_buffer[..len]) {
+            Ok(Some(request)) if !auth::is_authorized(&request) => {
+                log::warn!("HTTP | Unauthorized request from {}", remote_endpoint_addr);
+                (
+                    router::route_label(request.path),
+                    unauthorized_response(&request),
+                )
             }
-            match args.get("mac_addr") {
-                Some(v) => {
-                    wol_command(stack, v).await?;
-                    Ok(html_responses::WOL_SUCCESS)
-                }
-                None => Ok(html_responses::WOL_INPUT),
+            Ok(Some(request)) => {
+                log::info!("HTTP | {} {}", request.method.as_str(), request.path);
+                (
+                    router::route_label(request.path),
+                    router::handle_request(stack, &request).await,
+                )
             }
+            Ok(None) => (
+                "unknown",
+                Response::html(StatusCode::BadRequest, html_responses::BAD_REQUEST),
+            ),
+            Err(e) => (
+                "unknown",
+                Response::html(e.status(), html_responses::BAD_REQUEST),
+            ),
+        },
+        Ok(None) => {
+            log::info!("HTTP | Connection closed");
+            return Err(());
         }
-        "/switch" => {
-            if SWITCH_ENABLE != "true" && SWITCH_ENABLE != "1" {
-                return Ok(html_responses::NOT_ENABLED);
-            }
-            match args.get("gpio") {
-                Some(v) => {
-                    switch_command(v).await?;
-                    Ok(html_responses::SWITCH_SUCCESS)
-                }
-                None => Ok(html_responses::SWITCH_SELECT),
-            }
+        Err(ReadError::Connection) => {
+            log::error!(
+                "HTTP | Connection to {} failed while reading the request",
+                remote_endpoint_addr
+            );
+            return Err(());
+        }
+        Err(ReadError::Invalid(e)) => {
+            log::warn!("HTTP | Invalid request: {:?}", e);
+            (
+                "unknown",
+                Response::html(e.status(), html_responses::BAD_REQUEST),
+            )
+        }
+    };
+
+    // Streams need a subscriber to the events before the headers are sent
+    let mut subscriber = None;
+    if response.content_type.is_stream() {
+        subscriber = events_utils::open_stream();
+        if subscriber.is_none() {
+            log::warn!("HTTP | Too many streams open");
+            response = Response::html(StatusCode::ServiceUnavailable, html_responses::UNAVAILABLE);
+        }
+    }
+    record_http_request(route, response.status.code());
+
+    if write_response(&mut connection, &response).await.is_err() {
+        log::error!("HTTP | Error writing response");
+        if let Some(v) = subscriber {
+            events_utils::close_stream(v);
+        }
+        return Err(());
+    }
+
+    if let Some(v) = subscriber {
+        log::info!("HTTP | Streaming events to {}", remote_endpoint_addr);
+        if response.content_type == ContentType::WebSocket {
+            websocket_utils::run_session(stack, &mut connection, v).await;
+        } else {
+            events_utils::stream_events(&mut connection, v).await;
+        }
+    }
+
+    log::info!("HTTP | Closing connection to {}", remote_endpoint_addr);
+    connection.close().await;
+    Ok(())
+}
+
+/// The errors that can occur while reading a request.
+#[derive(Debug)]
+enum ReadError {
+    /// The request is invalid and must be answered with an error.
+    Invalid(ParseError),
+    /// The connection failed before the request was complete.
+    Connection,
+}
+
+/// Read from the connection until a complete request has been received.
+/// Returns the number of bytes read, or `None` if the connection was closed
+/// before the request was complete.
+async fn read_request(
+    connection: &mut Connection<'_, '_>,
+    buffer: &mut [u8; REQUEST_BUFFER_SIZE],
+) -> Result<Option<usize>, ReadError> {
+    let mut len = 0;
+    loop {
+        match connection.read(&mut buffer[len..]).await {
+            Ok(0) => return Ok(None),
+            Ok(n) => len += n,
+            Err(_) => return Err(ReadError::Connection),
+        }
+
+        match request::parse(&buffer[..len]) {
+            Ok(Some(_)) => return Ok(Some(len)),
+            Ok(None) if len < buffer.len() => continue,
+            Ok(None) => return Err(ReadError::Invalid(ParseError::PayloadTooLarge)),
+            Err(e) => return Err(ReadError::Invalid(e)),
         }
-        _ => Ok(html_responses::HOME),
     }
 }
 
-// Generate a HTTP response with the given html and write it to the buffer.
-// The total length of the response must be less than the buffer size.
-// Returns the total length of the response.
-fn generate_http_response(
-    html_content: &[u8],
-    buffer: &mut [u8; TCP_BUFFER_SIZE],
-) -> Result<usize, &'static str> {
-    let total_length = HTTP_HEADERS.len()
-        + HTML_HEADER.len()
-        + html_content.len()
-        + HTML_MENU.len()
-        + HTML_TAIL.len();
-
-    if total_length > TCP_BUFFER_SIZE {
-        return Err("Response does not fit in TCP buffer");
+/// Generate the response to an unauthorized request, challenging the client for credentials.
+fn unauthorized_response(request: &Request<'_>) -> Response {
+    let mut response = router::error_response(request, StatusCode::Unauthorized);
+
+    if auth::basic_enabled() {
+        response = response.with_header(auth::BASIC_CHALLENGE);
+    }
+    if auth::bearer_enabled() {
+        response = response.with_header(auth::BEARER_CHALLENGE);
+    }
+    response
+}
+
+/// Generate the HTML response matching the result of a command.
+fn command_response(result: Result<(), CommandError>, success: &'static [u8]) -> Response {
+    match result {
+        Ok(_) => Response::html(StatusCode::Ok, success),
+        Err(CommandError::InvalidInput) => {
+            Response::html(StatusCode::BadRequest, html_responses::BAD_REQUEST)
+        }
+        Err(CommandError::Failed) => {
+            Response::html(StatusCode::InternalServerError, html_responses::ERROR)
+        }
     }
+}
 
-    let mut offset = 0;
+/// Write the response to the connection part by part, without copying it to an intermediate buffer.
+/// HTML content is wrapped in the HTML header, menu and tail.
+async fn write_response(
+    connection: &mut Connection<'_, '_>,
+    response: &Response,
+) -> Result<(), ()> {
+    // The length of the body is known before sending it, except for streams
+    let mut content_length_header = String::<32>::new();
+    if !response.content_type.is_stream() {
+        let content_length: usize = body_parts(response).map(|v| v.len()).sum();
+        write!(
+            content_length_header,
+            "Content-Length: {}\r\n",
+            content_length
+        )
+        .map_err(|_| ())?;
+    }
 
-    // Copy headers into buffer
-    buffer[offset..offset + HTTP_HEADERS.len()].copy_from_slice(HTTP_HEADERS);
-    offset += HTTP_HEADERS.len();
+    // List the allowed methods if needed
+    let mut allow_header = String::<64>::new();
+    if !response.allow.is_empty() {
+        let mut methods = response.allow.iter().map(|v| v.as_str());
+        write!(allow_header, "Allow: {}", methods.next().unwrap_or("")).map_err(|_| ())?;
+        methods
+            .try_for_each(|v| write!(allow_header, ", {}", v))
+            .map_err(|_| ())?;
+        allow_header.push_str("\r\n").map_err(|_| ())?;
+    }
 
-    // Copy html header into buffer
-    buffer[offset..offset + HTML_HEADER.len()].copy_from_slice(HTML_HEADER);
-    offset += HTML_HEADER.len();
+    let head = [
+        response.status.status_line(),
+        response.content_type.header(),
+        content_length_header.as_bytes(),
+        allow_header.as_bytes(),
+        response.dynamic_header.as_bytes(),
+    ];
+    let end = match response.content_type {
+        ContentType::WebSocket => WEBSOCKET_HEADERS,
+        _ => HTTP_HEADERS,
+    };
+    let parts = head
+        .into_iter()
+        .chain(response.headers.iter().copied())
+        .chain([end])
+        .chain(body_parts(response));
 
-    // Copy html content into buffer
-    buffer[offset..offset + html_content.len()].copy_from_slice(html_content);
-    offset += html_content.len();
+    connection.write_parts(parts).await
+}
 
-    // Copy html menu into buffer
-    buffer[offset..offset + HTML_MENU.len()].copy_from_slice(HTML_MENU);
-    offset += HTML_MENU.len();
+/// Iterate over the parts of the body of a response.
+/// HTML content is wrapped in the HTML header, the menu generated from the routes and the tail.
+fn body_parts(response: &Response) -> impl Iterator<Item = &[u8]> {
+    let is_html = response.content_type == ContentType::Html;
+    let menu_items = router::menu_routes().flat_map(|(route, label)| {
+        [
+            HTML_MENU_ITEM_START,
+            route.path.as_bytes(),
+            HTML_MENU_ITEM_MIDDLE,
+            label.as_bytes(),
+            HTML_MENU_ITEM_END,
+        ]
+    });
 
-    // Copy html tail into buffer
-    buffer[offset..offset + HTML_TAIL.len()].copy_from_slice(HTML_TAIL);
+    let before = [HTML_HEADER].into_iter().filter(move |_| is_html);
+    let menu = [HTML_MENU_HEAD]
+        .into_iter()
+        .chain(menu_items)
+        .chain([HTML_MENU_TAIL, HTML_TAIL])
+        .filter(move |_| is_html);
 
-    Ok(total_length)
+    before.chain([response.content.as_bytes()]).chain(menu)
 }
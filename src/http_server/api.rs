@@ -0,0 +1,117 @@
+use super::{
+    CommandError,
+    request::Method,
+    response::{Content, Response},
+    router::{Feature, Route},
+    status::StatusCode,
+};
+use crate::{DNS_ENABLE, state::get_state, utils::is_flag_enabled};
+use alloc::boxed::Box;
+use core::fmt::Write;
+use embassy_net::Stack;
+use embassy_time::Instant;
+use heapless::String;
+
+/// The prefix of all the endpoints of the JSON API.
+pub const API_PREFIX: &str = "/api/v1/";
+
+/// The routes of the JSON API that are not tied to a feature.
+pub const ROUTES: &[Route] = &[Route {
+    path: "/api/v1/status",
+    methods: &[Method::Get],
+    handler: |cx| Box::pin(async move { status(cx.stack) }),
+    feature: None,
+    menu_label: None,
+}];
+
+/// The JSON body of a successful command.
+const SUCCESS: &[u8] = b"{\"status\":\"ok\"}";
+
+/// Generate the JSON response matching the result of a command.
+pub fn command_response(
+    result: Result<(), CommandError>,
+    invalid_input_message: &'static str,
+    failed_message: &'static str,
+) -> Response {
+    match result {
+        Ok(_) => Response::json(StatusCode::Ok, Content::Static(SUCCESS)),
+        Err(CommandError::InvalidInput) => error(StatusCode::BadRequest, invalid_input_message),
+        Err(CommandError::Failed) => error(StatusCode::InternalServerError, failed_message),
+    }
+}
+
+/// Generate the status of the device.
+pub fn status(stack: Stack<'_>) -> Response {
+    let mut json = String::new();
+    if write_status(&mut json, stack).is_err() {
+        log::error!("HTTP | Status does not fit in response buffer");
+        return error(StatusCode::InternalServerError, "Could not generate status");
+    }
+    Response::json(StatusCode::Ok, Content::Dynamic(json))
+}
+
+/// Write the status of the device as a JSON object.
+fn write_status(json: &mut impl Write, stack: Stack<'_>) -> core::fmt::Result {
+    write!(
+        json,
+        "{{\"version\":\"{}\",\"uptime\":{},",
+        env!("CARGO_PKG_VERSION"),
+        Instant::now().as_secs()
+    )?;
+    match stack.config_v4() {
+        Some(config) => {
+            write!(json, "\"ip\":\"{}\",", config.address.address())?;
+            match config.gateway {
+                Some(v) => write!(json, "\"gateway\":\"{}\",", v)?,
+                None => write!(json, "\"gateway\":null,")?,
+            }
+        }
+        None => write!(json, "\"ip\":null,\"gateway\":null,")?,
+    }
+    match stack.config_v6() {
+        Some(config) => write!(json, "\"ipv6\":\"{}\",", config.address.address())?,
+        None => write!(json, "\"ipv6\":null,")?,
+    }
+    match get_state().dns_update {
+        Some(v) => write!(
+            json,
+            "\"dns_update\":{{\"success\":{},\"message\":\"{}\",\"age\":{},\"stopped\":{}}},",
+            v.success,
+            v.message,
+            (Instant::now() - v.time).as_secs(),
+            v.stopped
+        )?,
+        None => write!(json, "\"dns_update\":null,")?,
+    }
+    match get_state().dns_verified {
+        Some(v) => write!(json, "\"dns_verified\":{},", v)?,
+        None => write!(json, "\"dns_verified\":null,")?,
+    }
+    match get_state().dns_verified_ipv6 {
+        Some(v) => write!(json, "\"dns_verified_ipv6\":{},", v)?,
+        None => write!(json, "\"dns_verified_ipv6\":null,")?,
+    }
+    write!(
+        json,
+        "\"features\":{{\"dns\":{},\"wol\":{},\"switch\":{}}}}}",
+        is_flag_enabled(DNS_ENABLE),
+        Feature::Wol.is_enabled(),
+        Feature::Switch.is_enabled()
+    )
+}
+
+/// Generate a JSON error object with the given status and message.
+pub fn error(status: StatusCode, message: &'static str) -> Response {
+    let mut json = String::new();
+    if write!(
+        json,
+        "{{\"error\":{{\"code\":{},\"message\":\"{}\"}}}}",
+        status.code(),
+        message
+    )
+    .is_err()
+    {
+        log::error!("HTTP | Error message does not fit in response buffer");
+    }
+    Response::json(status, Content::Dynamic(json))
+}
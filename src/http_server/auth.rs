@@ -0,0 +1,78 @@
+use super::request::Request;
+use crate::utils::{constant_time_eq, decode_base64, is_flag_enabled};
+
+/// The enable flag for the authentication.
+const HTTP_AUTH_ENABLE: &str = env!("HTTP_AUTH_ENABLE");
+/// The username for HTTP Basic authentication. Leave empty to disable Basic authentication.
+const HTTP_AUTH_USERNAME: &str = env!("HTTP_AUTH_USERNAME");
+/// The password for HTTP Basic authentication.
+const HTTP_AUTH_PASSWORD: &str = env!("HTTP_AUTH_PASSWORD");
+/// The token for Bearer authentication. Leave empty to disable Bearer authentication.
+const HTTP_AUTH_TOKEN: &str = env!("HTTP_AUTH_TOKEN");
+/// The maximum size of the decoded Basic credentials.
+const MAX_CREDENTIALS_SIZE: usize = 128;
+
+/// The challenge sent to clients when Basic authentication is enabled.
+pub const BASIC_CHALLENGE: &[u8] =
+    b"WWW-Authenticate: Basic realm=\"wakesp\", charset=\"UTF-8\"\r\n";
+/// The challenge sent to clients when Bearer authentication is enabled.
+pub const BEARER_CHALLENGE: &[u8] = b"WWW-Authenticate: Bearer realm=\"wakesp\"\r\n";
+
+/// Check if authentication is enabled.
+pub fn is_enabled() -> bool {
+    is_flag_enabled(HTTP_AUTH_ENABLE)
+}
+
+/// Check if Basic authentication is enabled.
+pub fn basic_enabled() -> bool {
+    is_enabled() && !HTTP_AUTH_USERNAME.is_empty()
+}
+
+/// Check if Bearer authentication is enabled.
+pub fn bearer_enabled() -> bool {
+    is_enabled() && !HTTP_AUTH_TOKEN.is_empty()
+}
+
+/// Check if the request is allowed to access the server.
+/// If authentication is enabled without any credentials, every request is denied.
+pub fn is_authorized(request: &Request<'_>) -> bool {
+    if !is_enabled() {
+        return true;
+    }
+
+    let Some(authorization) = request.header("Authorization") else {
+        return false;
+    };
+    let (scheme, credentials) = authorization.split_once(' ').unwrap_or((authorization, ""));
+    let credentials = credentials.trim();
+
+    if scheme.eq_ignore_ascii_case("Basic") && basic_enabled() {
+        check_basic(credentials)
+    } else if scheme.eq_ignore_ascii_case("Bearer") && bearer_enabled() {
+        constant_time_eq(credentials.as_bytes(), HTTP_AUTH_TOKEN.as_bytes())
+    } else {
+        false
+    }
+}
+
+/// Check base64 encoded `username:password` credentials.
+fn check_basic(credentials: &str) -> bool {
+    let mut decoded = [0u8; MAX_CREDENTIALS_SIZE];
+    let len = match decode_base64(credentials, &mut decoded) {
+        Ok(v) => v,
+        Err(_) => {
+            log::warn!("HTTP | Could not decode Basic credentials");
+            return false;
+        }
+    };
+
+    let Some(separator) = decoded[..len].iter().position(|&c| c == b':') else {
+        return false;
+    };
+    let (username, password) = (&decoded[..separator], &decoded[separator + 1..len]);
+
+    // Compare both fields to avoid leaking which one is wrong
+    let username_ok = constant_time_eq(username, HTTP_AUTH_USERNAME.as_bytes());
+    let password_ok = constant_time_eq(password, HTTP_AUTH_PASSWORD.as_bytes());
+    username_ok & password_ok
+}
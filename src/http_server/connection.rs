@@ -0,0 +1,209 @@
+use crate::utils::write_tcp_parts;
+use embassy_net::tcp::{TcpReader, TcpSocket, TcpWriter};
+use embassy_time::{Duration, with_timeout};
+#[cfg(feature = "https")]
+use {
+    crate::tls::TcpStream,
+    mbedtls_rs::{
+        Session, SessionRead, SessionWrite,
+        io::{Read, Write},
+    },
+};
+
+/// The size of the buffer gathering the parts of a response into TLS records.
+#[cfg(feature = "https")]
+const TLS_RECORD_BUFFER_SIZE: usize = 1024;
+
+/// A connection to a client of the HTTP server.
+pub enum Connection<'a, 's> {
+    /// A plain text TCP connection.
+    Plain(&'a mut TcpSocket<'s>),
+    /// A TCP connection encrypted with TLS, after the handshake.
+    #[cfg(feature = "https")]
+    Tls(Session<'a, TcpStream<'a, 's>>),
+}
+
+impl Connection<'_, '_> {
+    /// Read from the connection into the buffer.
+    /// Returns the number of bytes read, which is 0 if the connection was closed.
+    ///
+    /// A TLS read must not be cancelled, as it can leave the session in a partial state.
+    /// Use the halves of a split connection to race reads against other futures.
+    pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize, ()> {
+        match self {
+            Connection::Plain(socket) => socket.read(buf).await.map_err(|e| {
+                log::error!("HTTP | Error reading from connection: {:?}", e);
+            }),
+            #[cfg(feature = "https")]
+            Connection::Tls(session) => session.read(buf).await.map_err(|e| {
+                log::error!("HTTP | Error reading from TLS connection: {:?}", e);
+            }),
+        }
+    }
+
+    /// Writes a buffer to the connection.
+    pub async fn write_buf(&mut self, buf: &[u8]) -> Result<(), ()> {
+        self.write_parts([buf]).await
+    }
+
+    /// Writes multiple buffers to the connection one after the other, then flushes it.
+    /// On TLS connections, the buffers are gathered into records to avoid sending tiny records.
+    pub async fn write_parts<'p>(
+        &mut self,
+        parts: impl IntoIterator<Item = &'p [u8]>,
+    ) -> Result<(), ()> {
+        match self {
+            Connection::Plain(socket) => write_tcp_parts(socket, parts).await,
+            #[cfg(feature = "https")]
+            Connection::Tls(session) => write_tls_parts(session, parts).await,
+        }
+    }
+
+    /// Close the connection gracefully.
+    /// TLS connections notify the client before the socket is closed.
+    pub async fn close(self) {
+        match self {
+            Connection::Plain(socket) => socket.close(),
+            #[cfg(feature = "https")]
+            Connection::Tls(mut session) => {
+                if with_timeout(Duration::from_millis(500), session.close())
+                    .await
+                    .is_err()
+                {
+                    log::warn!("HTTP | TLS connection took too long to close");
+                }
+                session.stream().socket.close();
+            }
+        }
+    }
+}
+
+/// The read half of a split connection, which reads while the write half writes.
+pub trait ConnectionRead {
+    /// Read from the connection into the buffer.
+    /// Returns the number of bytes read, which is 0 if the connection was closed.
+    ///
+    /// Cancelling a read does not lose data, so it can be raced against other futures.
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, ()>;
+}
+
+/// The write half of a split connection.
+pub trait ConnectionWrite {
+    /// Writes multiple buffers to the connection one after the other, then flushes it.
+    async fn write_parts<'p>(
+        &mut self,
+        parts: impl IntoIterator<Item = &'p [u8]>,
+    ) -> Result<(), ()>;
+}
+
+impl ConnectionRead for TcpReader<'_> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, ()> {
+        TcpReader::read(self, buf).await.map_err(|e| {
+            log::error!("HTTP | Error reading from connection: {:?}", e);
+        })
+    }
+}
+
+impl ConnectionWrite for TcpWriter<'_> {
+    async fn write_parts<'p>(
+        &mut self,
+        parts: impl IntoIterator<Item = &'p [u8]>,
+    ) -> Result<(), ()> {
+        for mut buf in parts {
+            while !buf.is_empty() {
+                match self.write(buf).await {
+                    Ok(0) => log::warn!("HTTP | TCP connection wrote 0 bytes"),
+                    Ok(n) => buf = &buf[n..],
+                    Err(e) => {
+                        log::error!("HTTP | Error writing to connection: {:?}", e);
+                        return Err(());
+                    }
+                }
+            }
+        }
+
+        match with_timeout(Duration::from_millis(500), self.flush()).await {
+            Ok(Ok(_)) => Ok(()),
+            Ok(Err(e)) => {
+                log::error!("HTTP | Error flushing connection: {:?}", e);
+                Err(())
+            }
+            Err(_) => {
+                log::error!("HTTP | Connection took too long to flush");
+                Err(())
+            }
+        }
+    }
+}
+
+#[cfg(feature = "https")]
+impl<T: Read> ConnectionRead for SessionRead<'_, T> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, ()> {
+        // Unlike the reads of a whole session, the reads of its read half are cancel-safe
+        SessionRead::read(self, buf).await.map_err(|e| {
+            log::error!("HTTP | Error reading from TLS connection: {:?}", e);
+        })
+    }
+}
+
+#[cfg(feature = "https")]
+impl<T: Write> ConnectionWrite for SessionWrite<'_, T> {
+    async fn write_parts<'p>(
+        &mut self,
+        parts: impl IntoIterator<Item = &'p [u8]>,
+    ) -> Result<(), ()> {
+        write_tls_parts(self, parts).await
+    }
+}
+
+/// Writes multiple buffers to a TLS session, or its write half, then flushes it.
+/// The buffers are gathered into records to avoid sending tiny records.
+#[cfg(feature = "https")]
+async fn write_tls_parts<'p>(
+    writer: &mut impl Write,
+    parts: impl IntoIterator<Item = &'p [u8]>,
+) -> Result<(), ()> {
+    let mut record = [0u8; TLS_RECORD_BUFFER_SIZE];
+    let mut len = 0;
+    for mut buf in parts {
+        while !buf.is_empty() {
+            if len == record.len() {
+                write_tls_buf(writer, &record).await?;
+                len = 0;
+            }
+            let n = buf.len().min(record.len() - len);
+            record[len..len + n].copy_from_slice(&buf[..n]);
+            len += n;
+            buf = &buf[n..];
+        }
+    }
+    write_tls_buf(writer, &record[..len]).await?;
+
+    match with_timeout(Duration::from_millis(500), writer.flush()).await {
+        Ok(Ok(_)) => Ok(()),
+        Ok(Err(e)) => {
+            log::error!("HTTP | Error flushing TLS connection: {:?}", e);
+            Err(())
+        }
+        Err(_) => {
+            log::error!("HTTP | TLS connection took too long to flush");
+            Err(())
+        }
+    }
+}
+
+/// Write a whole buffer to a TLS session.
+#[cfg(feature = "https")]
+async fn write_tls_buf(writer: &mut impl Write, mut buf: &[u8]) -> Result<(), ()> {
+    while !buf.is_empty() {
+        match writer.write(buf).await {
+            Ok(0) => log::warn!("HTTP | TLS session wrote 0 bytes"),
+            Ok(n) => buf = &buf[n..],
+            Err(e) => {
+                log::error!("HTTP | Error writing to TLS connection: {:?}", e);
+                return Err(());
+            }
+        }
+    }
+    Ok(())
+}
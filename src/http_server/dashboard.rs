@@ -0,0 +1,141 @@
+use super::{
+    auth, html_responses,
+    response::{ContentType, Response},
+    router::Feature,
+    status::StatusCode,
+};
+use crate::{
+    DNS_ENABLE, SSID,
+    state::get_state,
+    utils::{get_wifi_rssi, is_flag_enabled},
+};
+use core::fmt::{self, Display, Write};
+use embassy_net::Stack;
+use embassy_time::Instant;
+use heapless::String;
+
+/// Generate the home page showing the state of the device.
+pub fn home_page(stack: Stack<'_>) -> Response {
+    let mut html = String::new();
+    if write_dashboard(&mut html, stack).is_err() {
+        log::error!("HTTP | Dashboard does not fit in response buffer");
+        return Response::html(StatusCode::InternalServerError, html_responses::ERROR);
+    }
+    Response::dynamic(StatusCode::Ok, ContentType::Html, html)
+}
+
+/// Write the state of the device as HTML.
+fn write_dashboard(html: &mut impl Write, stack: Stack<'_>) -> fmt::Result {
+    let state = get_state();
+    let now = Instant::now();
+
+    writeln!(html, "<h1>Wakesp</h1>")?;
+    writeln!(html, "<p>Hostname: {}</p>", Escaped(state.hostname))?;
+
+    match stack.config_v4() {
+        Some(config) => {
+            writeln!(html, "<p>IP address: {}</p>", config.address)?;
+            match config.gateway {
+                Some(v) => writeln!(html, "<p>Gateway: {}</p>", v)?,
+                None => writeln!(html, "<p>Gateway: unknown</p>")?,
+            }
+        }
+        None => writeln!(html, "<p>IP address: none</p>")?,
+    }
+    if let Some(config) = stack.config_v6() {
+        writeln!(html, "<p>IPv6 address: {}</p>", config.address)?;
+    }
+
+    match get_wifi_rssi() {
+        Some(v) => writeln!(html, "<p>Wi-Fi: {} ({} dBm)</p>", Escaped(SSID), v)?,
+        None => writeln!(html, "<p>Wi-Fi: {} (disconnected)</p>", Escaped(SSID))?,
+    }
+
+    writeln!(html, "<p>Uptime: {}</p>", Uptime(now.as_secs()))?;
+    writeln!(html, "<p>Free heap: {} bytes</p>", esp_alloc::HEAP.free())?;
+
+    if is_flag_enabled(DNS_ENABLE) {
+        match state.public_ip {
+            Some(v) => writeln!(html, "<p>Public IP: {}</p>", v)?,
+            None => writeln!(html, "<p>Public IP: unknown</p>")?,
+        }
+        if let Some(v) = state.public_ipv6 {
+            writeln!(html, "<p>Public IPv6: {}</p>", v)?;
+        }
+        match state.dns_update {
+            Some(v) => {
+                writeln!(
+                    html,
+                    "<p>Last DNS update: {} {} ago ({})</p>",
+                    if v.success { "success" } else { "failure" },
+                    Uptime((now - v.time).as_secs()),
+                    Escaped(v.message)
+                )?;
+                if v.stopped {
+                    writeln!(
+                        html,
+                        "<p>DNS updates stopped until the configuration changes</p>"
+                    )?;
+                }
+            }
+            None => writeln!(html, "<p>Last DNS update: never</p>")?,
+        }
+        for (verified, record, ip) in [
+            (state.dns_verified, "A", "IP"),
+            (state.dns_verified_ipv6, "AAAA", "IPv6"),
+        ] {
+            match verified {
+                Some(true) => writeln!(html, "<p>DNS {} record: verified</p>", record)?,
+                Some(false) => writeln!(
+                    html,
+                    "<p>Warning: DNS {} record does not resolve to the public {}</p>",
+                    record, ip
+                )?,
+                None => (),
+            }
+        }
+    }
+
+    write!(html, "<p>Features: HTTP")?;
+    for (enabled, name) in [
+        (is_flag_enabled(DNS_ENABLE), "DNS"),
+        (Feature::Wol.is_enabled(), "WOL"),
+        (Feature::Switch.is_enabled(), "Switch"),
+        (auth::is_enabled(), "Auth"),
+    ] {
+        if enabled {
+            write!(html, ", {}", name)?;
+        }
+    }
+    write!(html, "</p>")
+}
+
+/// Formats a duration in seconds as days, hours, minutes and seconds.
+struct Uptime(u64);
+
+impl Display for Uptime {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (days, rest) = (self.0 / 86400, self.0 % 86400);
+        let (hours, rest) = (rest / 3600, rest % 3600);
+        let (minutes, seconds) = (rest / 60, rest % 60);
+        write!(f, "{}d {:02}h {:02}m {:02}s", days, hours, minutes, seconds)
+    }
+}
+
+/// Escapes the HTML special characters of a string.
+struct Escaped<'a>(&'a str);
+
+impl Display for Escaped<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '<' => f.write_str("&lt;")?,
+                '>' => f.write_str("&gt;")?,
+                '&' => f.write_str("&amp;")?,
+                '"' => f.write_str("&quot;")?,
+                _ => f.write_char(c)?,
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,102 @@
+use super::{
+    connection::Connection,
+    get_http_task_count,
+    request::Method,
+    response::{ContentType, Response},
+    router::Route,
+    status::StatusCode,
+};
+use crate::events::{Event, EventSubscriber, subscribe};
+use alloc::boxed::Box;
+use core::{
+    cell::RefCell,
+    fmt::{self, Write},
+};
+use embassy_futures::select::{Either, select};
+use embassy_sync::{
+    blocking_mutex::{CriticalSectionMutex, Mutex, raw::CriticalSectionRawMutex},
+    pubsub::WaitResult,
+};
+use embassy_time::{Duration, Timer};
+use heapless::String;
+
+/// The interval between two keep-alive comments sent on an idle stream.
+/// It must be shorter than the timeout of the HTTP server sockets.
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(5);
+/// The maximum size of a formatted event.
+const EVENT_BUFFER_SIZE: usize = 128;
+
+/// The routes of the event stream.
+pub const ROUTES: &[Route] = &[Route {
+    path: "/events",
+    methods: &[Method::Get],
+    handler: |_| Box::pin(async { events_page() }),
+    feature: None,
+    menu_label: None,
+}];
+
+/// The number of event streams and WebSocket sessions currently open.
+static OPEN_STREAMS: Mutex<CriticalSectionRawMutex, RefCell<usize>> =
+    CriticalSectionMutex::new(RefCell::new(0));
+
+/// Handle the event stream endpoint.
+/// The events are sent by `stream_events` once the headers are written.
+pub fn events_page() -> Response {
+    Response::stream(StatusCode::Ok, ContentType::EventStream)
+        .with_header(b"Cache-Control: no-cache\r\n")
+}
+
+/// Open a stream of events, for an event stream or a WebSocket session,
+/// if there are enough HTTP server tasks left for the other requests.
+pub fn open_stream() -> Option<EventSubscriber> {
+    let max_streams = get_http_task_count().saturating_sub(1);
+    OPEN_STREAMS.lock(|open| {
+        let mut open = open.borrow_mut();
+        if *open >= max_streams {
+            return None;
+        }
+        let subscriber = subscribe()?;
+        *open += 1;
+        Some(subscriber)
+    })
+}
+
+/// Send the events to the client as Server-Sent Events until the connection is closed.
+pub async fn stream_events(connection: &mut Connection<'_, '_>, mut subscriber: EventSubscriber) {
+    loop {
+        let mut buf = String::<EVENT_BUFFER_SIZE>::new();
+        let formatted =
+            match select(subscriber.next_message(), Timer::after(KEEP_ALIVE_INTERVAL)).await {
+                Either::First(WaitResult::Message(event)) => write_event(&mut buf, &event),
+                Either::First(WaitResult::Lagged(n)) => {
+                    log::warn!("HTTP | Event stream missed {} events", n);
+                    continue;
+                }
+                Either::Second(_) => buf.push_str(": keep-alive\n\n").map_err(|_| fmt::Error),
+            };
+
+        if formatted.is_err() {
+            log::error!("HTTP | Event does not fit in buffer");
+            continue;
+        }
+        if connection.write_buf(buf.as_bytes()).await.is_err() {
+            log::info!("HTTP | Event stream closed");
+            break;
+        }
+    }
+
+    close_stream(subscriber);
+}
+
+/// Close a stream of events, making room for a new one.
+pub fn close_stream(subscriber: EventSubscriber) {
+    drop(subscriber);
+    OPEN_STREAMS.lock(|open| *open.borrow_mut() -= 1);
+}
+
+/// Write an event in the Server-Sent Events format, with a JSON payload.
+fn write_event(buf: &mut impl Write, event: &Event) -> fmt::Result {
+    write!(buf, "event: {}\ndata: ", event.name())?;
+    event.write_json(buf)?;
+    buf.write_str("\n\n")
+}
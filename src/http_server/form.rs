@@ -0,0 +1,259 @@
+use super::{
+    request::{REQUEST_BUFFER_SIZE, Request},
+    status::StatusCode,
+};
+use core::ops::Range;
+use heapless::Vec;
+
+/// The maximum size of the decoded keys and values of a form.
+/// Decoding never makes a field longer, so the query and the body of any accepted request fit.
+const FORM_BUFFER_SIZE: usize = REQUEST_BUFFER_SIZE;
+/// The maximum number of fields in a form.
+const MAX_FIELDS: usize = 8;
+
+/// The errors that can occur while decoding a form.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FormError {
+    /// A percent escape is invalid or the decoded text is not UTF-8.
+    InvalidEncoding,
+    /// The form has too many fields or is too big to be decoded.
+    TooLarge,
+    /// The query string has too many fields.
+    QueryTooLarge,
+}
+
+impl FormError {
+    /// The status code to answer with when this error occurs.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            FormError::InvalidEncoding => StatusCode::BadRequest,
+            FormError::TooLarge => StatusCode::PayloadTooLarge,
+            FormError::QueryTooLarge => StatusCode::UriTooLong,
+        }
+    }
+}
+
+/// The decoded fields of an `application/x-www-form-urlencoded` form.
+#[derive(Default)]
+pub struct Form {
+    data: Vec<u8, FORM_BUFFER_SIZE>,
+    fields: Vec<(Range<usize>, Range<usize>), MAX_FIELDS>,
+}
+
+impl Form {
+    /// Decode the fields of the query string and, if it is a form, of the body of a request.
+    pub fn from_request(request: &Request<'_>) -> Result<Self, FormError> {
+        let mut form = Self::default();
+        form.decode(request.query.as_bytes()).map_err(|e| match e {
+            FormError::TooLarge => FormError::QueryTooLarge,
+            e => e,
+        })?;
+
+        let is_form = request.header("Content-Type").is_some_and(|v| {
+            v.split(';').next().is_some_and(|v| {
+                v.trim()
+                    .eq_ignore_ascii_case("application/x-www-form-urlencoded")
+            })
+        });
+        if is_form {
+            form.decode(request.body)?;
+        }
+
+        Ok(form)
+    }
+
+    /// Decode the fields of an urlencoded string and add them to the form.
+    /// Fields are separated by `&` and keys are separated from values by `=`.
+    pub fn decode(&mut self, encoded: &[u8]) -> Result<(), FormError> {
+        for field in encoded.split(|&c| c == b'&').filter(|v| !v.is_empty()) {
+            let (key, value) = match field.iter().position(|&c| c == b'=') {
+                Some(i) => (&field[..i], &field[i + 1..]),
+                None => (field, &[][..]),
+            };
+
+            let key = self.decode_component(key)?;
+            let value = self.decode_component(value)?;
+            self.fields
+                .push((key, value))
+                .map_err(|_| FormError::TooLarge)?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the value of the first field with the given key.
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(k, _)| self.str_at(k) == key)
+            .map(|(_, v)| self.str_at(v))
+    }
+
+    /// Decode a key or value into the data buffer and return its position.
+    fn decode_component(&mut self, encoded: &[u8]) -> Result<Range<usize>, FormError> {
+        let start = self.data.len();
+        let mut bytes = encoded.iter();
+
+        while let Some(&c) = bytes.next() {
+            let decoded = match c {
+                b'+' => b' ',
+                b'%' => {
+                    let high = bytes.next().and_then(|&v| hex_value(v));
+                    let low = bytes.next().and_then(|&v| hex_value(v));
+                    match (high, low) {
+                        (Some(high), Some(low)) => (high << 4) | low,
+                        _ => return Err(FormError::InvalidEncoding),
+                    }
+                }
+                _ => c,
+            };
+            self.data.push(decoded).map_err(|_| FormError::TooLarge)?;
+        }
+
+        if core::str::from_utf8(&self.data[start..]).is_err() {
+            return Err(FormError::InvalidEncoding);
+        }
+
+        Ok(start..self.data.len())
+    }
+
+    /// Get the decoded text at the given position.
+    fn str_at(&self, range: &Range<usize>) -> &str {
+        // Every component is validated as UTF-8 when it is decoded
+        core::str::from_utf8(&self.data[range.clone()]).unwrap_or("")
+    }
+}
+
+/// Get the value of an hexadecimal digit.
+fn hex_value(c: u8) -> Option<u8> {
+    match c {
+        b'0'..=b'9' => Some(c - b'0'),
+        b'a'..=b'f' => Some(c - b'a' + 10),
+        b'A'..=b'F' => Some(c - b'A' + 10),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::http_server::request::{self, MAX_BODY_SIZE, MAX_HEADER_SIZE};
+
+    /// Decode an urlencoded string into a new form.
+    fn decode(encoded: &str) -> Result<Form, FormError> {
+        let mut form = Form::default();
+        form.decode(encoded.as_bytes())?;
+        Ok(form)
+    }
+
+    /// Decode the form of a request with the given target, content type and body.
+    fn from_request(target: &str, content_type: &str, body: &str) -> Result<Form, FormError> {
+        let buf = format!(
+            "POST {} HTTP/1.1\r\nContent-Type: {}\r\nContent-Length: {}\r\n\r\n{}",
+            target,
+            content_type,
+            body.len(),
+            body
+        );
+        let request = request::parse(buf.as_bytes()).unwrap().unwrap();
+        Form::from_request(&request)
+    }
+
+    #[test]
+    fn decodes_plus_and_percent_escapes() {
+        let form = decode("name=a+b&mac%5Faddr=00%3A11%3a22&text=%20%2B%25").unwrap();
+        assert_eq!(form.get("name"), Some("a b"));
+        assert_eq!(form.get("mac_addr"), Some("00:11:22"));
+        assert_eq!(form.get("text"), Some(" +%"));
+        assert_eq!(form.get("missing"), None);
+    }
+
+    #[test]
+    fn decodes_utf8() {
+        let form = decode("name=caf%C3%A9").unwrap();
+        assert_eq!(form.get("name"), Some("caf\u{e9}"));
+    }
+
+    #[test]
+    fn rejects_invalid_encoding() {
+        for encoded in [
+            "a=%zz", "a=%4", "a=%", "a=%4g", "%ff=a", "a=%C3", "a=%C3%28",
+        ] {
+            assert_eq!(
+                decode(encoded).err(),
+                Some(FormError::InvalidEncoding),
+                "{:?}",
+                encoded
+            );
+        }
+    }
+
+    #[test]
+    fn keeps_empty_keys_and_values() {
+        let form = decode("&=value&key=&flag&&").unwrap();
+        assert_eq!(form.fields.len(), 3);
+        assert_eq!(form.get(""), Some("value"));
+        assert_eq!(form.get("key"), Some(""));
+        assert_eq!(form.get("flag"), Some(""));
+    }
+
+    #[test]
+    fn returns_first_value_of_key() {
+        let form = decode("a=1&a=2&b=3=4").unwrap();
+        assert_eq!(form.get("a"), Some("1"));
+        assert_eq!(form.get("b"), Some("3=4"));
+    }
+
+    #[test]
+    fn merges_query_and_body() {
+        let content_type = "application/x-www-form-urlencoded; charset=UTF-8";
+        let form = from_request("/wol?a=1&b=2", content_type, "b=3&c=4").unwrap();
+        assert_eq!(form.get("a"), Some("1"));
+        assert_eq!(form.get("b"), Some("2"));
+        assert_eq!(form.get("c"), Some("4"));
+    }
+
+    #[test]
+    fn ignores_body_of_other_content_types() {
+        let form = from_request("/wol?a=1", "application/json", "c=4").unwrap();
+        assert_eq!(form.get("a"), Some("1"));
+        assert_eq!(form.get("c"), None);
+    }
+
+    #[test]
+    fn limits_number_of_fields() {
+        let fields = ["a=1"; MAX_FIELDS].join("&");
+        assert_eq!(decode(&fields).unwrap().fields.len(), MAX_FIELDS);
+
+        let fields = ["a=1"; MAX_FIELDS + 1].join("&");
+        assert_eq!(decode(&fields).err(), Some(FormError::TooLarge));
+        assert_eq!(FormError::TooLarge.status().code(), 413);
+    }
+
+    #[test]
+    fn limits_number_of_query_fields() {
+        let target = format!("/wol?{}", ["a=1"; MAX_FIELDS + 1].join("&"));
+        let result = from_request(&target, "text/plain", "");
+        assert_eq!(result.err(), Some(FormError::QueryTooLarge));
+        assert_eq!(FormError::QueryTooLarge.status().code(), 414);
+    }
+
+    #[test]
+    fn decodes_query_longer_than_body_limit() {
+        let target = format!("/wol?a={}", "b".repeat(MAX_HEADER_SIZE - 128));
+        let form = from_request(&target, "text/plain", "").unwrap();
+        assert_eq!(form.get("a").map(|v| v.len()), Some(MAX_HEADER_SIZE - 128));
+    }
+
+    #[test]
+    fn decodes_largest_body() {
+        let content_type = "application/x-www-form-urlencoded";
+        let body = format!("a={}", "b".repeat(MAX_BODY_SIZE - 2));
+        let form = from_request("/wol", content_type, &body).unwrap();
+        assert_eq!(form.get("a").map(|v| v.len()), Some(MAX_BODY_SIZE - 2));
+
+        // The decoded query and body together still fit
+        let form = from_request("/wol?c=d", content_type, &body).unwrap();
+        assert_eq!(form.get("c"), Some("d"));
+    }
+}
@@ -1,5 +1,3 @@
-pub const HOME: &[u8] = b"";
-
 pub const SWITCH_SUCCESS: &[u8] = b"\
 <h1>Switch</h1>
 <p>Switch activated!</p>";
@@ -7,7 +5,7 @@ pub const SWITCH_SUCCESS: &[u8] = b"\
 pub const SWITCH_SELECT: &[u8] = b"\
 <h1>Switch</h1>
 <p>Select the pin to use as a power switch</p>
-<form method=\"get\">
+<form method=\"post\">
   <div>
     <label for=\"gpio2\">GPIO 2</label>
     <input type=\"radio\" id=\"gpio2\" name=\"gpio\" value=\"2\" />
@@ -39,7 +37,7 @@ pub const SWITCH_SELECT: &[u8] = b"\
 pub const WOL_INPUT: &[u8] = b"\
 <h1>WOL</h1>
 <p>Insert the MAC address of the device to wake</p>
-<form method=\"get\">
+<form method=\"post\">
   <div>
     <label for=\"mac_addr\">Mac Address:</label>
     <input type=\"text\" id=\"mac_addr\" name=\"mac_addr\" />
@@ -55,25 +53,58 @@ pub const ERROR: &[u8] = b"\
 <h1>Error</h1>
 <p>An error occured while handling the query</p>";
 
+pub const BAD_REQUEST: &[u8] = b"\
+<h1>Error</h1>
+<p>The query is invalid</p>";
+
+pub const UNAUTHORIZED: &[u8] = b"\
+<h1>Error</h1>
+<p>Authentication is required to access this device</p>";
+
+pub const NOT_FOUND: &[u8] = b"\
+<h1>Error</h1>
+<p>This page does not exist</p>";
+
+pub const METHOD_NOT_ALLOWED: &[u8] = b"\
+<h1>Error</h1>
+<p>This method is not allowed on this page</p>";
+
 pub const NOT_ENABLED: &[u8] = b"\
 <h1>Error</h1>
 <p>This service is not enabled on this device</p>";
 
-pub const HTML_MENU: &[u8] = b"\
+pub const UNAVAILABLE: &[u8] = b"\
+<h1>Error</h1>
+<p>The device is busy, try again later</p>";
+
+pub const CROSS_ORIGIN: &[u8] = b"\
+<h1>Error</h1>
+<p>Connections from other websites are not allowed</p>";
+
+#[cfg(feature = "https")]
+pub const MOVED_TO_HTTPS: &[u8] = b"\
+<h1>Moved</h1>
+<p>This page is served over HTTPS</p>";
+
+pub const HTML_MENU_HEAD: &[u8] = b"\
 \r\n<br />
 <ol>
-  <h1>Menu</h1>
-  <li>
-    <a class=\"arrow\" href=\"/wol\"
-      ><i class=\"fas fa-arrow-alt-right\"></i>WOL</a
-    >
-  </li>
+  <h1>Menu</h1>\n";
+
+pub const HTML_MENU_ITEM_START: &[u8] = b"\
   <li>
-    <a class=\"arrow\" href=\"/switch\"
-      ><i class=\"fas fa-arrow-alt-right\"></i>Switch</a
+    <a class=\"arrow\" href=\"";
+
+pub const HTML_MENU_ITEM_MIDDLE: &[u8] = b"\
+\"
+      ><i class=\"fas fa-arrow-alt-right\"></i>";
+
+pub const HTML_MENU_ITEM_END: &[u8] = b"\
+</a
     >
-  </li>
-</ol>\r\n";
+  </li>\n";
+
+pub const HTML_MENU_TAIL: &[u8] = b"</ol>\r\n";
 
 pub const HTML_HEADER: &[u8] = b"\
 <!doctype html>
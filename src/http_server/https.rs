@@ -0,0 +1,119 @@
+use super::{
+    connection::Connection,
+    html_responses,
+    request::Request,
+    response::{DYNAMIC_HEADER_SIZE, Response},
+    status::StatusCode,
+};
+use crate::{
+    tls::{TcpStream, get_tls, pem_file},
+    utils::is_flag_enabled,
+};
+use core::{ffi::CStr, fmt::Write};
+use embassy_net::tcp::TcpSocket;
+use heapless::String;
+use mbedtls_rs::{
+    Certificate, Credentials, PrivateKey, ServerSessionConfig, Session, SessionConfig, X509,
+};
+
+/// The port on which the device will listen for HTTPS requests.
+const HTTPS_LISTEN_PORT: &str = env!("HTTPS_LISTEN_PORT");
+/// The fallback port on which the device will listen for HTTPS requests.
+const HTTPS_LISTEN_PORT_FALLBACK: u16 = 443;
+/// The flag enabling the plain HTTP listener redirecting the clients to HTTPS.
+const HTTPS_REDIRECT_ENABLE: &str = env!("HTTPS_REDIRECT_ENABLE");
+
+/// The certificate of the server in the PEM format, relative to the root of the project.
+/// It is included in the firmware at build time.
+const CERTIFICATE: &CStr = pem_file(concat!(
+    include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/",
+        env!("HTTPS_CERT_PATH")
+    )),
+    "\0"
+));
+/// The private key of the certificate in the PEM format, relative to the root of the project.
+/// It is included in the firmware at build time.
+const PRIVATE_KEY: &CStr = pem_file(concat!(
+    include_str!(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/",
+        env!("HTTPS_KEY_PATH")
+    )),
+    "\0"
+));
+
+/// Get the port on which the device listens for HTTPS requests.
+pub fn get_https_listen_port() -> u16 {
+    match HTTPS_LISTEN_PORT.parse::<u16>() {
+        Ok(v) => v,
+        Err(e) => {
+            log::error!("HTTP | Could not parse HTTPS port number: {:?}", e);
+            log::error!(
+                "HTTP | Using default HTTPS port {}",
+                HTTPS_LISTEN_PORT_FALLBACK
+            );
+            HTTPS_LISTEN_PORT_FALLBACK
+        }
+    }
+}
+
+/// Check if the plain HTTP listener redirecting the clients to HTTPS is enabled.
+pub fn redirect_enabled() -> bool {
+    is_flag_enabled(HTTPS_REDIRECT_ENABLE)
+}
+
+/// Perform the TLS handshake with the client on an accepted socket.
+pub async fn accept<'a, 's>(socket: &'a mut TcpSocket<'s>) -> Result<Connection<'a, 's>, ()> {
+    let tls = get_tls().ok_or_else(|| log::error!("HTTP | TLS is not initialized"))?;
+
+    let certificate = Certificate::new(X509::PEM(CERTIFICATE))
+        .map_err(|e| log::error!("HTTP | Invalid HTTPS certificate: {:?}", e))?;
+    let private_key = PrivateKey::new(X509::PEM(PRIVATE_KEY), None)
+        .map_err(|e| log::error!("HTTP | Invalid HTTPS private key: {:?}", e))?;
+    let config = SessionConfig::Server(ServerSessionConfig::new(Credentials {
+        certificate,
+        private_key,
+    }));
+
+    let mut session = Session::new(tls, TcpStream::new(socket), &config)
+        .map_err(|e| log::error!("HTTP | Could not create TLS session: {:?}", e))?;
+    session
+        .connect()
+        .await
+        .map_err(|e| log::warn!("HTTP | TLS handshake failed: {:?}", e))?;
+
+    Ok(Connection::Tls(session))
+}
+
+/// Redirect a request received over plain HTTP to the same page over HTTPS.
+/// `fallback_host` is used when the request has no `Host` header.
+pub fn redirect_response(request: &Request<'_>, fallback_host: &str, https_port: u16) -> Response {
+    // Replace the port of the host, taking care of the colons in IPv6 addresses
+    let host = request.header("Host").unwrap_or(fallback_host).trim();
+    let host = match host.rfind(':') {
+        Some(i) if !host[i..].contains(']') => &host[..i],
+        _ => host,
+    };
+
+    let mut location = String::<DYNAMIC_HEADER_SIZE>::new();
+    let formatted = write!(location, "Location: https://{}", host)
+        .and_then(|_| match https_port {
+            HTTPS_LISTEN_PORT_FALLBACK => Ok(()),
+            port => write!(location, ":{}", port),
+        })
+        .and_then(|_| location.write_str(request.path))
+        .and_then(|_| match request.query {
+            "" => Ok(()),
+            query => write!(location, "?{}", query),
+        })
+        .and_then(|_| location.write_str("\r\n"));
+
+    if formatted.is_err() {
+        log::warn!("HTTP | Redirect location does not fit in buffer");
+        return Response::html(StatusCode::BadRequest, html_responses::BAD_REQUEST);
+    }
+    Response::html(StatusCode::MovedPermanently, html_responses::MOVED_TO_HTTPS)
+        .with_dynamic_header(location)
+}
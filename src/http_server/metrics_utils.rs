@@ -0,0 +1,123 @@
+use super::{
+    html_responses,
+    request::Method,
+    response::{ContentType, Response},
+    router::{Feature, Route},
+    status::StatusCode,
+};
+use crate::metrics::{FIRST_SWITCH_PIN, MAX_HTTP_SERIES, Metrics, read_metrics};
+use alloc::{boxed::Box, string::String};
+use core::fmt::{self, Write};
+use embassy_time::Instant;
+
+/// The enable flag for the metrics feature.
+pub const METRICS_ENABLE: &str = env!("METRICS_ENABLE");
+/// The size of the metrics, without the HTTP request series.
+const BASE_METRICS_SIZE: usize = 1536;
+/// The maximum size of the line of an HTTP request series, for routes of up to 32 bytes.
+const HTTP_SERIES_SIZE: usize = 96;
+/// The buffer size for the metrics, which fits every HTTP request series that can be tracked.
+/// It is allocated on the heap, as it is only needed while the response is sent.
+const METRICS_BUFFER_SIZE: usize = BASE_METRICS_SIZE + MAX_HTTP_SERIES * HTTP_SERIES_SIZE;
+
+/// The routes of the metrics feature.
+pub const ROUTES: &[Route] = &[Route {
+    path: "/metrics",
+    methods: &[Method::Get],
+    handler: |_| Box::pin(async { metrics_page() }),
+    feature: Some(Feature::Metrics),
+    menu_label: None,
+}];
+
+/// Handle the metrics endpoint, in the Prometheus text exposition format.
+pub fn metrics_page() -> Response {
+    // The metrics are copied to format them outside of the critical section
+    let metrics = read_metrics(|v| v.clone());
+
+    let mut text = String::new();
+    if text.try_reserve(METRICS_BUFFER_SIZE).is_err() {
+        log::error!("HTTP | Not enough memory for the metrics");
+        return Response::html(StatusCode::InternalServerError, html_responses::ERROR);
+    }
+    if write_metrics(&mut text, &metrics, Instant::now()).is_err() {
+        log::error!("HTTP | Error formatting the metrics");
+        return Response::html(StatusCode::InternalServerError, html_responses::ERROR);
+    }
+    Response::allocated(StatusCode::Ok, ContentType::Text, text)
+}
+
+/// Write the metrics in the Prometheus text exposition format.
+fn write_metrics(text: &mut impl Write, metrics: &Metrics, now: Instant) -> fmt::Result {
+    write_header(text, "wakesp_uptime_seconds", "gauge")?;
+    writeln!(text, "wakesp_uptime_seconds {}", now.as_secs())?;
+
+    write_header(text, "wakesp_heap_used_bytes", "gauge")?;
+    writeln!(text, "wakesp_heap_used_bytes {}", esp_alloc::HEAP.used())?;
+    write_header(text, "wakesp_heap_free_bytes", "gauge")?;
+    writeln!(text, "wakesp_heap_free_bytes {}", esp_alloc::HEAP.free())?;
+
+    write_header(text, "wakesp_wifi_disconnects_total", "counter")?;
+    writeln!(
+        text,
+        "wakesp_wifi_disconnects_total {}",
+        metrics.wifi_disconnects
+    )?;
+
+    write_header(text, "wakesp_wol_packets_total", "counter")?;
+    writeln!(
+        text,
+        "wakesp_wol_packets_total{{result=\"sent\"}} {}",
+        metrics.wol_sent
+    )?;
+    writeln!(
+        text,
+        "wakesp_wol_packets_total{{result=\"failed\"}} {}",
+        metrics.wol_failed
+    )?;
+
+    write_header(text, "wakesp_switch_pulses_total", "counter")?;
+    for (i, count) in metrics.switch_pulses.iter().enumerate() {
+        writeln!(
+            text,
+            "wakesp_switch_pulses_total{{gpio=\"{}\"}} {}",
+            FIRST_SWITCH_PIN as usize + i,
+            count
+        )?;
+    }
+
+    write_header(text, "wakesp_dns_updates_total", "counter")?;
+    writeln!(
+        text,
+        "wakesp_dns_updates_total{{result=\"success\"}} {}",
+        metrics.dns_update_successes
+    )?;
+    writeln!(
+        text,
+        "wakesp_dns_updates_total{{result=\"failure\"}} {}",
+        metrics.dns_update_failures
+    )?;
+    if let Some(v) = metrics.dns_last_change {
+        write_header(text, "wakesp_dns_last_change_uptime_seconds", "gauge")?;
+        writeln!(
+            text,
+            "wakesp_dns_last_change_uptime_seconds {}",
+            v.as_secs()
+        )?;
+    }
+
+    write_header(text, "wakesp_http_requests_total", "counter")?;
+    for ((route, status), count) in metrics.http_requests.iter() {
+        writeln!(
+            text,
+            "wakesp_http_requests_total{{route=\"{}\",status=\"{}\"}} {}",
+            route, status, count
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Write the type of a metric.
+fn write_header(text: &mut impl Write, name: &str, kind: &str) -> fmt::Result {
+    writeln!(text, "# TYPE {} {}", name, kind)
+}
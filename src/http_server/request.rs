@@ -0,0 +1,311 @@
+use super::status::StatusCode;
+use heapless::Vec;
+
+/// The maximum size of the request line and headers.
+pub const MAX_HEADER_SIZE: usize = 2048;
+/// The maximum size of the request body.
+pub const MAX_BODY_SIZE: usize = 1024;
+/// The buffer size needed to hold a complete request.
+pub const REQUEST_BUFFER_SIZE: usize = MAX_HEADER_SIZE + MAX_BODY_SIZE;
+/// The maximum number of headers in a request.
+const MAX_HEADERS: usize = 16;
+
+/// The HTTP method of a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    Get,
+    Head,
+    Post,
+    Put,
+    Delete,
+    Options,
+    Other,
+}
+
+impl Method {
+    /// The name of the method.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Method::Get => "GET",
+            Method::Head => "HEAD",
+            Method::Post => "POST",
+            Method::Put => "PUT",
+            Method::Delete => "DELETE",
+            Method::Options => "OPTIONS",
+            Method::Other => "OTHER",
+        }
+    }
+
+    fn from_str(method: &str) -> Self {
+        match method {
+            "GET" => Method::Get,
+            "HEAD" => Method::Head,
+            "POST" => Method::Post,
+            "PUT" => Method::Put,
+            "DELETE" => Method::Delete,
+            "OPTIONS" => Method::Options,
+            _ => Method::Other,
+        }
+    }
+}
+
+/// The errors that can occur while parsing a request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseError {
+    /// The request is malformed.
+    BadRequest,
+    /// The body is bigger than `MAX_BODY_SIZE`.
+    PayloadTooLarge,
+    /// The request line and headers are bigger than `MAX_HEADER_SIZE`
+    /// or there are more than `MAX_HEADERS` headers.
+    HeadersTooLarge,
+}
+
+impl ParseError {
+    /// The status code to answer with when this error occurs.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ParseError::BadRequest => StatusCode::BadRequest,
+            ParseError::PayloadTooLarge => StatusCode::PayloadTooLarge,
+            ParseError::HeadersTooLarge => StatusCode::HeadersTooLarge,
+        }
+    }
+}
+
+/// A parsed HTTP/1.x request borrowing from the receive buffer.
+#[derive(Debug)]
+pub struct Request<'a> {
+    pub method: Method,
+    /// The path of the request target, without the query string.
+    pub path: &'a str,
+    /// The query string of the request target, without the `?`.
+    pub query: &'a str,
+    pub headers: Vec<(&'a str, &'a str), MAX_HEADERS>,
+    pub body: &'a [u8],
+}
+
+impl<'a> Request<'a> {
+    /// Get the value of a header. Header names are case-insensitive.
+    pub fn header(&self, name: &str) -> Option<&'a str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| *value)
+    }
+}
+
+/// Parse the bytes received so far.
+/// Returns `None` if more bytes are needed to complete the request.
+///
+/// The whole buffer is parsed again on each call, so this can be called
+/// after every read until it returns a request or an error.
+pub fn parse(buf: &[u8]) -> Result<Option<Request<'_>>, ParseError> {
+    // Find the end of the headers
+    let header_len = match find_subslice(buf, b"\r\n\r\n") {
+        Some(v) => v + 4,
+        None if buf.len() >= MAX_HEADER_SIZE => return Err(ParseError::HeadersTooLarge),
+        None => return Ok(None),
+    };
+    if header_len > MAX_HEADER_SIZE {
+        return Err(ParseError::HeadersTooLarge);
+    }
+
+    let head = core::str::from_utf8(&buf[..header_len - 4]).map_err(|_| ParseError::BadRequest)?;
+    let mut lines = head.split("\r\n");
+
+    // Parse the request line
+    let request_line = lines.next().ok_or(ParseError::BadRequest)?;
+    let mut parts = request_line.split(' ');
+    let (method, target, version) = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+        (Some(m), Some(t), Some(v), None) if !m.is_empty() && t.starts_with('/') => (m, t, v),
+        _ => return Err(ParseError::BadRequest),
+    };
+    if !version.starts_with("HTTP/1.") {
+        return Err(ParseError::BadRequest);
+    }
+    let (path, query) = target.split_once('?').unwrap_or((target, ""));
+
+    // Parse the headers
+    let mut headers = Vec::new();
+    for line in lines {
+        let (name, value) = line.split_once(':').ok_or(ParseError::BadRequest)?;
+        if name.is_empty() || name.contains(|c: char| c.is_ascii_whitespace()) {
+            return Err(ParseError::BadRequest);
+        }
+        headers
+            .push((name, value.trim()))
+            .map_err(|_| ParseError::HeadersTooLarge)?;
+    }
+
+    let mut request = Request {
+        method: Method::from_str(method),
+        path,
+        query,
+        headers,
+        body: &[],
+    };
+
+    // Chunked request bodies are not supported
+    if request.header("Transfer-Encoding").is_some() {
+        return Err(ParseError::BadRequest);
+    }
+
+    // Get the body if there is one
+    let body_len = match request.header("Content-Length") {
+        Some(v) => v.parse::<usize>().map_err(|_| ParseError::BadRequest)?,
+        None => 0,
+    };
+    if body_len > MAX_BODY_SIZE {
+        return Err(ParseError::PayloadTooLarge);
+    }
+    if buf.len() < header_len + body_len {
+        return Ok(None);
+    }
+    request.body = &buf[header_len..header_len + body_len];
+
+    Ok(Some(request))
+}
+
+/// Find the position of the first occurence of `needle` in `haystack`.
+fn find_subslice(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack
+        .windows(needle.len())
+        .position(|window| window == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parse a complete request, panicking if it is incomplete or invalid.
+    fn parse_complete(buf: &[u8]) -> Request<'_> {
+        parse(buf).unwrap().unwrap()
+    }
+
+    #[test]
+    fn parses_request_line_and_headers() {
+        let request = parse_complete(
+            b"GET /wol?mac_addr=00:11 HTTP/1.1\r\nHost: wakesp\r\nX-Test:  value \r\n\r\n",
+        );
+        assert_eq!(request.method, Method::Get);
+        assert_eq!(request.path, "/wol");
+        assert_eq!(request.query, "mac_addr=00:11");
+        assert_eq!(request.header("host"), Some("wakesp"));
+        assert_eq!(request.header("X-TEST"), Some("value"));
+        assert_eq!(request.header("Missing"), None);
+        assert!(request.body.is_empty());
+    }
+
+    #[test]
+    fn waits_for_headers_split_across_reads() {
+        let buf = b"GET / HTTP/1.1\r\nHost: wakesp\r\n\r\n";
+        for len in 0..buf.len() {
+            assert!(parse(&buf[..len]).unwrap().is_none(), "{} bytes", len);
+        }
+        assert_eq!(parse_complete(buf).path, "/");
+    }
+
+    #[test]
+    fn waits_for_body_split_across_reads() {
+        let buf = b"POST /wol HTTP/1.1\r\nContent-Length: 5\r\n\r\nhello";
+        for len in buf.len() - 5..buf.len() {
+            assert!(parse(&buf[..len]).unwrap().is_none(), "{} bytes", len);
+        }
+        let request = parse_complete(buf);
+        assert_eq!(request.method, Method::Post);
+        assert_eq!(request.body, b"hello");
+    }
+
+    #[test]
+    fn ignores_bytes_after_body() {
+        let request = parse_complete(b"POST / HTTP/1.1\r\nContent-Length: 2\r\n\r\nabcd");
+        assert_eq!(request.body, b"ab");
+    }
+
+    #[test]
+    fn rejects_oversized_headers() {
+        // Without the end of the headers
+        let mut buf = [b'a'; MAX_HEADER_SIZE];
+        buf[..16].copy_from_slice(b"GET / HTTP/1.1\r\n");
+        assert_eq!(parse(&buf).unwrap_err(), ParseError::HeadersTooLarge);
+
+        // With the end of the headers past the limit
+        let mut buf = [b'a'; MAX_HEADER_SIZE + 4];
+        buf[..16].copy_from_slice(b"GET / HTTP/1.1\r\n");
+        buf[16..20].copy_from_slice(b"X: a");
+        buf[MAX_HEADER_SIZE..].copy_from_slice(b"\r\n\r\n");
+        assert_eq!(parse(&buf).unwrap_err(), ParseError::HeadersTooLarge);
+    }
+
+    #[test]
+    fn rejects_too_many_headers() {
+        let mut buf = heapless::String::<1024>::new();
+        buf.push_str("GET / HTTP/1.1\r\n").unwrap();
+        for _ in 0..MAX_HEADERS {
+            buf.push_str("X: a\r\n").unwrap();
+        }
+        let mut limit = buf.clone();
+        limit.push_str("\r\n").unwrap();
+        assert!(parse(limit.as_bytes()).unwrap().is_some());
+
+        buf.push_str("X: a\r\n\r\n").unwrap();
+        assert_eq!(
+            parse(buf.as_bytes()).unwrap_err(),
+            ParseError::HeadersTooLarge
+        );
+    }
+
+    #[test]
+    fn rejects_oversized_body() {
+        let buf = b"POST / HTTP/1.1\r\nContent-Length: 1025\r\n\r\n";
+        assert_eq!(parse(buf).unwrap_err(), ParseError::PayloadTooLarge);
+        assert_eq!(ParseError::PayloadTooLarge.status().code(), 413);
+    }
+
+    #[test]
+    fn rejects_bad_content_length() {
+        for value in ["abc", "-1", "1 2", "", "18446744073709551616"] {
+            let mut buf = heapless::String::<128>::new();
+            buf.push_str("POST / HTTP/1.1\r\nContent-Length: ").unwrap();
+            buf.push_str(value).unwrap();
+            buf.push_str("\r\n\r\n").unwrap();
+            assert_eq!(
+                parse(buf.as_bytes()).unwrap_err(),
+                ParseError::BadRequest,
+                "{:?}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_transfer_encoding() {
+        let buf = b"POST / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhello\r\n0\r\n\r\n";
+        assert_eq!(parse(buf).unwrap_err(), ParseError::BadRequest);
+    }
+
+    #[test]
+    fn rejects_malformed_requests() {
+        for buf in [
+            &b"GET /\r\n\r\n"[..],
+            b"GET / HTTP/1.1 extra\r\n\r\n",
+            b"GET index.html HTTP/1.1\r\n\r\n",
+            b"GET / HTTP/2\r\n\r\n",
+            b" / HTTP/1.1\r\n\r\n",
+            b"GET / HTTP/1.1\r\nNo colon\r\n\r\n",
+            b"GET / HTTP/1.1\r\nBad name: a\r\n\r\n",
+            b"GET / HTTP/1.1\r\n: a\r\n\r\n",
+            b"GET /\xff HTTP/1.1\r\n\r\n",
+        ] {
+            assert_eq!(parse(buf).unwrap_err(), ParseError::BadRequest, "{:?}", buf);
+        }
+    }
+
+    #[test]
+    fn keeps_unknown_methods() {
+        assert_eq!(
+            parse_complete(b"PATCH / HTTP/1.0\r\n\r\n").method,
+            Method::Other
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use super::{request::Method, status::StatusCode};
+use alloc::string::String as HeapString;
+use heapless::{String, Vec};
+
+/// The maximum size of a dynamically generated response content.
+pub const DYNAMIC_CONTENT_SIZE: usize = 2048;
+/// The maximum number of extra headers in a response.
+const MAX_HEADERS: usize = 4;
+/// The maximum size of a dynamically generated header line.
+/// It must fit a `Location` header with the host and path of a request.
+pub const DYNAMIC_HEADER_SIZE: usize = 256;
+
+/// The type of the content of a response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentType {
+    /// An HTML page, wrapped in the HTML header, menu and tail.
+    Html,
+    /// A JSON document, sent as is.
+    Json,
+    /// Plain text in the Prometheus exposition format, sent as is.
+    Text,
+    /// A stream of Server-Sent Events, sent without a length until the connection is closed.
+    EventStream,
+    /// No content, the connection is upgraded to a WebSocket.
+    WebSocket,
+}
+
+impl ContentType {
+    /// The `Content-Type` header for this content type.
+    pub fn header(&self) -> &'static [u8] {
+        match self {
+            ContentType::Html => b"Content-Type: text/html; charset=utf-8\r\n",
+            ContentType::Json => b"Content-Type: application/json\r\n",
+            ContentType::Text => b"Content-Type: text/plain; version=0.0.4; charset=utf-8\r\n",
+            ContentType::EventStream => b"Content-Type: text/event-stream\r\n",
+            ContentType::WebSocket => b"",
+        }
+    }
+
+    /// Check if the content is streamed after the headers instead of having a known length.
+    pub fn is_stream(&self) -> bool {
+        matches!(self, ContentType::EventStream | ContentType::WebSocket)
+    }
+}
+
+/// The content of a response.
+#[allow(clippy::large_enum_variant)]
+pub enum Content {
+    Static(&'static [u8]),
+    Dynamic(String<DYNAMIC_CONTENT_SIZE>),
+    /// Content too big for `DYNAMIC_CONTENT_SIZE`, allocated on the heap for this response only.
+    Allocated(HeapString),
+}
+
+impl Content {
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Content::Static(v) => v,
+            Content::Dynamic(v) => v.as_bytes(),
+            Content::Allocated(v) => v.as_bytes(),
+        }
+    }
+}
+
+/// A response to an HTTP request.
+pub struct Response {
+    pub status: StatusCode,
+    pub content_type: ContentType,
+    pub content: Content,
+    /// Extra header lines, each ending with `\r\n`.
+    pub headers: Vec<&'static [u8], MAX_HEADERS>,
+    /// The methods listed in the `Allow` header, if any.
+    pub allow: &'static [Method],
+    /// An extra header line generated for this response, ending with `\r\n`, if not empty.
+    pub dynamic_header: String<DYNAMIC_HEADER_SIZE>,
+}
+
+impl Response {
+    /// Create an HTML response from a static page.
+    pub fn html(status: StatusCode, page: &'static [u8]) -> Self {
+        Self {
+            status,
+            content_type: ContentType::Html,
+            content: Content::Static(page),
+            headers: Vec::new(),
+            allow: &[],
+            dynamic_header: String::new(),
+        }
+    }
+
+    /// Create a response from dynamically generated content.
+    pub fn dynamic(
+        status: StatusCode,
+        content_type: ContentType,
+        content: String<DYNAMIC_CONTENT_SIZE>,
+    ) -> Self {
+        Self {
+            status,
+            content_type,
+            content: Content::Dynamic(content),
+            headers: Vec::new(),
+            allow: &[],
+            dynamic_header: String::new(),
+        }
+    }
+
+    /// Create a response from dynamically generated content allocated on the heap.
+    pub fn allocated(status: StatusCode, content_type: ContentType, content: HeapString) -> Self {
+        Self {
+            status,
+            content_type,
+            content: Content::Allocated(content),
+            headers: Vec::new(),
+            allow: &[],
+            dynamic_header: String::new(),
+        }
+    }
+
+    /// Create a response whose content is streamed after the headers.
+    pub fn stream(status: StatusCode, content_type: ContentType) -> Self {
+        Self {
+            status,
+            content_type,
+            content: Content::Static(b""),
+            headers: Vec::new(),
+            allow: &[],
+            dynamic_header: String::new(),
+        }
+    }
+
+    /// Create a JSON response.
+    pub fn json(status: StatusCode, content: Content) -> Self {
+        Self {
+            status,
+            content_type: ContentType::Json,
+            content,
+            headers: Vec::new(),
+            allow: &[],
+            dynamic_header: String::new(),
+        }
+    }
+
+    /// Add a header line, ending with `\r\n`, to the response.
+    pub fn with_header(mut self, header: &'static [u8]) -> Self {
+        if self.headers.push(header).is_err() {
+            log::error!("HTTP | Too many headers in response");
+        }
+        self
+    }
+
+    /// Add a dynamically generated header line, ending with `\r\n`, to the response.
+    pub fn with_dynamic_header(mut self, header: String<DYNAMIC_HEADER_SIZE>) -> Self {
+        self.dynamic_header = header;
+        self
+    }
+
+    /// List the allowed methods in the `Allow` header of the response.
+    pub fn with_allow(mut self, methods: &'static [Method]) -> Self {
+        self.allow = methods;
+        self
+    }
+}
@@ -0,0 +1,157 @@
+use super::{
+    api, dashboard, events_utils,
+    form::Form,
+    html_responses, metrics_utils,
+    request::{Method, Request},
+    response::Response,
+    status::StatusCode,
+    switch_utils, websocket_utils, wol_utils,
+};
+use crate::utils::is_flag_enabled;
+use alloc::boxed::Box;
+use core::pin::Pin;
+use embassy_net::Stack;
+
+/// A feature of the device that must be enabled for a route to be served.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feature {
+    Wol,
+    Switch,
+    Metrics,
+}
+
+impl Feature {
+    /// Check if the feature is enabled in the configuration.
+    pub fn is_enabled(&self) -> bool {
+        match self {
+            Feature::Wol => is_flag_enabled(wol_utils::WOL_ENABLE),
+            Feature::Switch => is_flag_enabled(switch_utils::SWITCH_ENABLE),
+            Feature::Metrics => is_flag_enabled(metrics_utils::METRICS_ENABLE),
+        }
+    }
+}
+
+/// The request given to the handler of a route.
+pub struct Context<'a> {
+    pub stack: Stack<'a>,
+    pub request: &'a Request<'a>,
+    /// The fields of the query string and of the form body.
+    pub args: &'a Form,
+}
+
+/// The future returned by a handler.
+/// It is boxed so that the handlers of every module have the same type.
+pub type HandlerFuture<'a> = Pin<Box<dyn Future<Output = Response> + 'a>>;
+
+/// The function handling the requests of a route.
+pub type Handler = for<'a> fn(&'a Context<'a>) -> HandlerFuture<'a>;
+
+/// An endpoint of the HTTP server.
+pub struct Route {
+    pub path: &'static str,
+    /// The methods supported by the route.
+    pub methods: &'static [Method],
+    pub handler: Handler,
+    /// The feature that must be enabled to serve the route.
+    pub feature: Option<Feature>,
+    /// The label of the route in the menu of the web interface, if it should be listed.
+    pub menu_label: Option<&'static str>,
+}
+
+/// The routes of the home page.
+const HOME_ROUTES: &[Route] = &[Route {
+    path: "/",
+    methods: &[Method::Get],
+    handler: |cx| Box::pin(async move { dashboard::home_page(cx.stack) }),
+    feature: None,
+    menu_label: None,
+}];
+
+/// The routes of every feature, in the order they appear in the menu.
+const ROUTES: &[&[Route]] = &[
+    HOME_ROUTES,
+    wol_utils::ROUTES,
+    switch_utils::ROUTES,
+    api::ROUTES,
+    metrics_utils::ROUTES,
+    events_utils::ROUTES,
+    websocket_utils::ROUTES,
+];
+
+/// Iterate over all the routes of the HTTP server.
+pub fn routes() -> impl Iterator<Item = &'static Route> {
+    ROUTES.iter().flat_map(|v| v.iter())
+}
+
+/// Iterate over the routes listed in the menu whose feature is enabled.
+pub fn menu_routes() -> impl Iterator<Item = (&'static Route, &'static str)> {
+    routes()
+        .filter(|route| route.feature.is_none_or(|v| v.is_enabled()))
+        .filter_map(|route| route.menu_label.map(|label| (route, label)))
+}
+
+/// Get the path of the route matching a request path, to be used as a metric label.
+/// Unknown paths share the same label to keep the number of series bounded.
+pub fn route_label(path: &str) -> &'static str {
+    routes()
+        .find(|route| route.path == path)
+        .map_or("unknown", |route| route.path)
+}
+
+/// Find the route of the request and call its handler.
+pub async fn handle_request(stack: Stack<'_>, request: &Request<'_>) -> Response {
+    let Some(route) = routes().find(|route| route.path == request.path) else {
+        return error_response(request, StatusCode::NotFound);
+    };
+
+    if !route.methods.contains(&request.method) {
+        return error_response(request, StatusCode::MethodNotAllowed).with_allow(route.methods);
+    }
+
+    if route.feature.is_some_and(|v| !v.is_enabled()) {
+        return error_response(request, StatusCode::Forbidden);
+    }
+
+    let args = match Form::from_request(request) {
+        Ok(v) => v,
+        Err(e) => {
+            log::warn!("HTTP | Could not decode query arguments: {:?}", e);
+            return error_response(request, e.status());
+        }
+    };
+
+    let cx = Context {
+        stack,
+        request,
+        args: &args,
+    };
+    (route.handler)(&cx).await
+}
+
+/// Generate an error response in the format expected by the client,
+/// which is JSON for the API and HTML for the web interface.
+pub fn error_response(request: &Request<'_>, status: StatusCode) -> Response {
+    if request.path.starts_with(api::API_PREFIX) {
+        let message = match status {
+            StatusCode::Unauthorized => "Authentication required",
+            StatusCode::Forbidden => "This feature is not enabled on this device",
+            StatusCode::NotFound => "Unknown API endpoint",
+            StatusCode::MethodNotAllowed => "Method not allowed",
+            StatusCode::InternalServerError => "Internal error",
+            StatusCode::ServiceUnavailable => "The device is busy",
+            _ => "Invalid request",
+        };
+        return api::error(status, message);
+    }
+
+    let page = match status {
+        StatusCode::Unauthorized => html_responses::UNAUTHORIZED,
+        StatusCode::Forbidden => html_responses::NOT_ENABLED,
+        StatusCode::NotFound => html_responses::NOT_FOUND,
+        StatusCode::MethodNotAllowed => html_responses::METHOD_NOT_ALLOWED,
+        StatusCode::InternalServerError => html_responses::ERROR,
+        StatusCode::ServiceUnavailable => html_responses::UNAVAILABLE,
+        _ => html_responses::BAD_REQUEST,
+    };
+    Response::html(status, page)
+}
@@ -0,0 +1,59 @@
+/// The HTTP status codes used by the server.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatusCode {
+    SwitchingProtocols,
+    Ok,
+    /// Only used to redirect the clients to HTTPS.
+    #[cfg_attr(not(feature = "https"), allow(dead_code))]
+    MovedPermanently,
+    BadRequest,
+    Unauthorized,
+    Forbidden,
+    NotFound,
+    MethodNotAllowed,
+    PayloadTooLarge,
+    UriTooLong,
+    HeadersTooLarge,
+    InternalServerError,
+    ServiceUnavailable,
+}
+
+impl StatusCode {
+    /// The numeric value of the status code.
+    pub fn code(&self) -> u16 {
+        match self {
+            StatusCode::SwitchingProtocols => 101,
+            StatusCode::Ok => 200,
+            StatusCode::MovedPermanently => 301,
+            StatusCode::BadRequest => 400,
+            StatusCode::Unauthorized => 401,
+            StatusCode::Forbidden => 403,
+            StatusCode::NotFound => 404,
+            StatusCode::MethodNotAllowed => 405,
+            StatusCode::PayloadTooLarge => 413,
+            StatusCode::UriTooLong => 414,
+            StatusCode::HeadersTooLarge => 431,
+            StatusCode::InternalServerError => 500,
+            StatusCode::ServiceUnavailable => 503,
+        }
+    }
+
+    /// The status line of a response with this status code.
+    pub fn status_line(&self) -> &'static [u8] {
+        match self {
+            StatusCode::SwitchingProtocols => b"HTTP/1.1 101 Switching Protocols\r\n",
+            StatusCode::Ok => b"HTTP/1.1 200 OK\r\n",
+            StatusCode::MovedPermanently => b"HTTP/1.1 301 Moved Permanently\r\n",
+            StatusCode::BadRequest => b"HTTP/1.1 400 Bad Request\r\n",
+            StatusCode::Unauthorized => b"HTTP/1.1 401 Unauthorized\r\n",
+            StatusCode::Forbidden => b"HTTP/1.1 403 Forbidden\r\n",
+            StatusCode::NotFound => b"HTTP/1.1 404 Not Found\r\n",
+            StatusCode::MethodNotAllowed => b"HTTP/1.1 405 Method Not Allowed\r\n",
+            StatusCode::PayloadTooLarge => b"HTTP/1.1 413 Payload Too Large\r\n",
+            StatusCode::UriTooLong => b"HTTP/1.1 414 URI Too Long\r\n",
+            StatusCode::HeadersTooLarge => b"HTTP/1.1 431 Request Header Fields Too Large\r\n",
+            StatusCode::InternalServerError => b"HTTP/1.1 500 Internal Server Error\r\n",
+            StatusCode::ServiceUnavailable => b"HTTP/1.1 503 Service Unavailable\r\n",
+        }
+    }
+}
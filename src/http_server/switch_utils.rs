@@ -1,17 +1,72 @@
-use crate::pins::*;
+use super::{
+    CommandError, api, command_response,
+    form::Form,
+    html_responses,
+    request::Method,
+    response::Response,
+    router::{Feature, Route},
+    status::StatusCode,
+};
+use crate::{
+    events::{Event, publish_event},
+    metrics::{FIRST_SWITCH_PIN, update_metrics},
+    pins::*,
+};
+use alloc::boxed::Box;
 use core::cell::RefCell;
 use embassy_sync::blocking_mutex::{Mutex, raw::CriticalSectionRawMutex};
 use embassy_time::{Duration, Timer};
 use esp_hal::gpio::{Level, OutputOpenDrain};
 
+/// The enable flag for the Switch feature.
+pub const SWITCH_ENABLE: &str = env!("SWITCH_ENABLE");
+
+/// The routes of the Switch feature.
+pub const ROUTES: &[Route] = &[
+    Route {
+        path: "/switch",
+        methods: &[Method::Get, Method::Post],
+        handler: |cx| Box::pin(switch_page(cx.args)),
+        feature: Some(Feature::Switch),
+        menu_label: Some("Switch"),
+    },
+    Route {
+        path: "/api/v1/switch",
+        methods: &[Method::Get, Method::Post],
+        handler: |cx| Box::pin(switch_api(cx.args)),
+        feature: Some(Feature::Switch),
+        menu_label: None,
+    },
+];
+
+/// Handle the Switch page of the web interface.
+pub async fn switch_page(args: &Form) -> Response {
+    match args.get("gpio") {
+        Some(v) => command_response(switch_command(v).await, html_responses::SWITCH_SUCCESS),
+        None => Response::html(StatusCode::Ok, html_responses::SWITCH_SELECT),
+    }
+}
+
+/// Handle the Switch endpoint of the JSON API.
+pub async fn switch_api(args: &Form) -> Response {
+    let Some(gpio) = args.get("gpio") else {
+        return api::error(StatusCode::BadRequest, "Missing parameter 'gpio'");
+    };
+    api::command_response(
+        switch_command(gpio).await,
+        "Invalid GPIO pin",
+        "Could not toggle GPIO pin",
+    )
+}
+
 /// Triggers a GPIO pin based on the provided pin number.
-pub async fn switch_command(pin_str: &str) -> Result<(), ()> {
+pub async fn switch_command(pin_str: &str) -> Result<(), CommandError> {
     // Parse the pin number as a u8
     let pin = match pin_str.parse::<u8>() {
         Ok(v) => v,
         Err(_) => {
             log::error!("Switch | Error parsing pin number");
-            return Err(());
+            return Err(CommandError::InvalidInput);
         }
     };
 
@@ -27,16 +82,18 @@ pub async fn switch_command(pin_str: &str) -> Result<(), ()> {
         9 => toggle_pin(&GPIO9, false).await,
         _ => {
             log::warn!("Switch | Invalid pin number '{}'", pin);
-            return Err(());
+            return Err(CommandError::InvalidInput);
         }
     };
 
     // Check if the pin was toggled successfully
     if result.is_err() {
         log::error!("Switch | Error toggling pin GPIO{}", pin_str);
-        return Err(());
+        return Err(CommandError::Failed);
     }
 
+    update_metrics(|metrics| metrics.switch_pulses[(pin - FIRST_SWITCH_PIN) as usize] += 1);
+    publish_event(Event::SwitchPulsed { pin });
     log::info!("SWITCH | Triggered pin GPIO{}", pin_str);
     Ok(())
 }
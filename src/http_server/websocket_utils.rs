@@ -0,0 +1,269 @@
+mod protocol;
+
+use super::{
+    api,
+    connection::{Connection, ConnectionRead, ConnectionWrite},
+    events_utils::close_stream,
+    form::Form,
+    html_responses,
+    request::{Method, Request},
+    response::{ContentType, DYNAMIC_CONTENT_SIZE, DYNAMIC_HEADER_SIZE, Response},
+    router::{self, Feature, Route},
+    status::StatusCode,
+    switch_utils, wol_utils,
+};
+use crate::{
+    events::{Event, EventSubscriber},
+    utils::decode_base64,
+};
+use alloc::boxed::Box;
+use core::fmt::Write;
+use embassy_futures::select::{Either3, select3};
+use embassy_net::Stack;
+use embassy_sync::pubsub::WaitResult;
+use embassy_time::{Duration, Timer};
+use heapless::String;
+use protocol::{
+    MAX_HEADER_SIZE, MAX_MESSAGE_SIZE, Message, OPCODE_CLOSE, OPCODE_PING, OPCODE_PONG,
+    OPCODE_TEXT, Received, accept_key, frame_header, parse_frame,
+};
+
+/// The interval between two pings sent to the client.
+/// It must be shorter than the timeout of the HTTP server sockets.
+const PING_INTERVAL: Duration = Duration::from_secs(5);
+/// The buffer size for the frames received from the client.
+/// It fits a frame of `MAX_MESSAGE_SIZE` bytes with the largest frame header.
+const RX_BUFFER_SIZE: usize = MAX_MESSAGE_SIZE + MAX_HEADER_SIZE;
+
+/// The routes of the WebSocket control channel.
+pub const ROUTES: &[Route] = &[Route {
+    path: "/ws",
+    methods: &[Method::Get],
+    handler: |cx| Box::pin(async move { websocket_page(cx.request) }),
+    feature: None,
+    menu_label: None,
+}];
+
+/// Handle the opening handshake of a WebSocket connection.
+/// The session is run by `run_session` once the headers are written.
+pub fn websocket_page(request: &Request<'_>) -> Response {
+    let is_upgrade = request
+        .header("Upgrade")
+        .is_some_and(|v| v.trim().eq_ignore_ascii_case("websocket"));
+    let is_connection_upgrade = request.header("Connection").is_some_and(|v| {
+        v.split(',')
+            .any(|v| v.trim().eq_ignore_ascii_case("upgrade"))
+    });
+    if !is_upgrade || !is_connection_upgrade {
+        log::warn!("HTTP | Request to the WebSocket endpoint is not an upgrade");
+        return router::error_response(request, StatusCode::BadRequest);
+    }
+
+    if request.header("Sec-WebSocket-Version").map(str::trim) != Some("13") {
+        log::warn!("HTTP | Unsupported WebSocket version");
+        return router::error_response(request, StatusCode::BadRequest)
+            .with_header(b"Sec-WebSocket-Version: 13\r\n");
+    }
+
+    // Browsers always send the origin of the page, which must be the device itself
+    let is_same_origin = match (request.header("Origin"), request.header("Host")) {
+        (None, _) => true,
+        (Some(origin), Some(host)) => origin
+            .split_once("://")
+            .is_some_and(|(_, v)| v.eq_ignore_ascii_case(host.trim())),
+        (Some(_), None) => false,
+    };
+    if !is_same_origin {
+        log::warn!("HTTP | Cross-origin WebSocket connection refused");
+        return Response::html(StatusCode::Forbidden, html_responses::CROSS_ORIGIN);
+    }
+
+    let key = request.header("Sec-WebSocket-Key").unwrap_or("").trim();
+    let mut decoded_key = [0u8; 16];
+    if decode_base64(key, &mut decoded_key) != Ok(16) {
+        log::warn!("HTTP | Invalid WebSocket key");
+        return router::error_response(request, StatusCode::BadRequest);
+    }
+
+    match accept_header(key) {
+        Ok(v) => Response::stream(StatusCode::SwitchingProtocols, ContentType::WebSocket)
+            .with_dynamic_header(v),
+        Err(_) => {
+            log::error!("HTTP | Could not generate WebSocket accept key");
+            router::error_response(request, StatusCode::InternalServerError)
+        }
+    }
+}
+
+/// Generate the header accepting the WebSocket connection from the key of the client.
+fn accept_header(key: &str) -> Result<String<DYNAMIC_HEADER_SIZE>, ()> {
+    let accept = accept_key(key)?;
+    let accept = core::str::from_utf8(&accept).map_err(|_| ())?;
+
+    let mut header = String::new();
+    write!(header, "Sec-WebSocket-Accept: {}\r\n", accept).map_err(|_| ())?;
+    Ok(header)
+}
+
+/// Run a WebSocket session until the connection is closed.
+/// Commands received from the client are answered in order, and the events of the device
+/// are sent as they happen.
+pub async fn run_session(
+    stack: Stack<'_>,
+    connection: &mut Connection<'_, '_>,
+    mut subscriber: EventSubscriber,
+) {
+    // The connection is split, as the reads are raced against the events and the pings
+    match connection {
+        Connection::Plain(socket) => {
+            let (mut reader, mut writer) = socket.split();
+            run_split_session(stack, &mut reader, &mut writer, &mut subscriber).await;
+        }
+        #[cfg(feature = "https")]
+        Connection::Tls(session) => match session.split().await {
+            Ok((mut reader, mut writer)) => {
+                run_split_session(stack, &mut reader, &mut writer, &mut subscriber).await
+            }
+            Err(e) => log::error!("HTTP | Error splitting TLS connection: {:?}", e),
+        },
+    }
+
+    log::info!("HTTP | WebSocket session closed");
+    close_stream(subscriber);
+}
+
+/// Run a WebSocket session on the halves of a split connection until it is closed.
+async fn run_split_session(
+    stack: Stack<'_>,
+    reader: &mut impl ConnectionRead,
+    writer: &mut impl ConnectionWrite,
+    subscriber: &mut EventSubscriber,
+) {
+    let mut rx_buffer = [0u8; RX_BUFFER_SIZE];
+    let mut len = 0;
+    let mut message = Message::default();
+
+    loop {
+        let status = match select3(
+            reader.read(&mut rx_buffer[len..]),
+            subscriber.next_message(),
+            Timer::after(PING_INTERVAL),
+        )
+        .await
+        {
+            Either3::First(Ok(0)) => break,
+            Either3::First(Ok(n)) => {
+                len += n;
+                handle_frames(stack, writer, &mut rx_buffer, &mut len, &mut message).await
+            }
+            Either3::First(Err(_)) => break,
+            Either3::Second(WaitResult::Message(event)) => send_event(writer, &event).await,
+            Either3::Second(WaitResult::Lagged(n)) => {
+                log::warn!("HTTP | WebSocket session missed {} events", n);
+                Ok(())
+            }
+            Either3::Third(_) => write_frame(writer, OPCODE_PING, &[]).await,
+        };
+
+        if status.is_err() {
+            break;
+        }
+    }
+}
+
+/// Handle all the complete frames in the buffer, then remove them from it.
+/// Returns an error if the session must end.
+async fn handle_frames(
+    stack: Stack<'_>,
+    writer: &mut impl ConnectionWrite,
+    buffer: &mut [u8; RX_BUFFER_SIZE],
+    len: &mut usize,
+    message: &mut Message,
+) -> Result<(), ()> {
+    loop {
+        let frame = match parse_frame(&mut buffer[..*len]) {
+            Ok(Some(v)) => v,
+            Ok(None) => return Ok(()),
+            Err(code) => return close(writer, code).await,
+        };
+        let payload = &buffer[frame.payload.clone()];
+
+        match message.receive(&frame, payload) {
+            Ok(Received::Nothing) => {}
+            Ok(Received::Ping(payload)) => write_frame(writer, OPCODE_PONG, payload).await?,
+            Ok(Received::Close(payload)) => {
+                write_frame(writer, OPCODE_CLOSE, payload).await?;
+                return Err(());
+            }
+            Ok(Received::Text(text)) => {
+                let response = handle_command(stack, text).await;
+                write_frame(writer, OPCODE_TEXT, response.content.as_bytes()).await?;
+            }
+            Err(code) => return close(writer, code).await,
+        }
+
+        buffer.copy_within(frame.len..*len, 0);
+        *len -= frame.len;
+    }
+}
+
+/// Run a command received from the client and generate its JSON response.
+/// Commands are urlencoded like the arguments of the API, e.g. `action=wol&mac_addr=...`.
+async fn handle_command(stack: Stack<'_>, text: &str) -> Response {
+    let mut args = Form::default();
+    if let Err(e) = args.decode(text.as_bytes()) {
+        log::warn!("HTTP | Could not decode WebSocket command: {:?}", e);
+        return api::error(StatusCode::BadRequest, "Invalid command");
+    }
+
+    let action = args.get("action");
+    let feature = match action {
+        Some("wol") => Some(Feature::Wol),
+        Some("switch") => Some(Feature::Switch),
+        _ => None,
+    };
+    if feature.is_some_and(|v| !v.is_enabled()) {
+        return api::error(
+            StatusCode::Forbidden,
+            "This feature is not enabled on this device",
+        );
+    }
+
+    match action {
+        Some("wol") => wol_utils::wol_api(stack, &args).await,
+        Some("switch") => switch_utils::switch_api(&args).await,
+        Some("status") => api::status(stack),
+        Some(_) => api::error(StatusCode::BadRequest, "Unknown action"),
+        None => api::error(StatusCode::BadRequest, "Missing parameter 'action'"),
+    }
+}
+
+/// Send an event to the client as a JSON object.
+async fn send_event(writer: &mut impl ConnectionWrite, event: &Event) -> Result<(), ()> {
+    let mut json = String::<DYNAMIC_CONTENT_SIZE>::new();
+    let formatted = write!(json, "{{\"event\":\"{}\",\"data\":", event.name())
+        .and_then(|_| event.write_json(&mut json))
+        .and_then(|_| json.write_str("}"));
+    if formatted.is_err() {
+        log::error!("HTTP | Event does not fit in buffer");
+        return Ok(());
+    }
+    write_frame(writer, OPCODE_TEXT, json.as_bytes()).await
+}
+
+/// Send a close frame with the given status code, ending the session.
+async fn close(writer: &mut impl ConnectionWrite, code: u16) -> Result<(), ()> {
+    log::warn!("HTTP | Closing WebSocket session with status {}", code);
+    write_frame(writer, OPCODE_CLOSE, &code.to_be_bytes()).await?;
+    Err(())
+}
+
+/// Write an unmasked frame with the given opcode and payload to the connection.
+async fn write_frame(
+    writer: &mut impl ConnectionWrite,
+    opcode: u8,
+    payload: &[u8],
+) -> Result<(), ()> {
+    let (header, header_len) = frame_header(opcode, payload.len()).ok_or(())?;
+    writer.write_parts([&header[..header_len], payload]).await
+}
@@ -0,0 +1,412 @@
+use crate::utils::{encode_base64, sha1};
+use core::ops::Range;
+use heapless::Vec;
+
+/// The GUID appended to the key of the client to compute the accept key, defined by RFC 6455.
+const WEBSOCKET_GUID: &[u8] = b"258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// The maximum size of a message received from the client.
+pub const MAX_MESSAGE_SIZE: usize = 256;
+/// The maximum size of the header of a frame received from the client.
+pub const MAX_HEADER_SIZE: usize = 14;
+/// The size of the accept key, which is the base64 encoded SHA-1 digest.
+pub const ACCEPT_KEY_SIZE: usize = 28;
+
+pub const OPCODE_CONTINUATION: u8 = 0x0;
+pub const OPCODE_TEXT: u8 = 0x1;
+pub const OPCODE_BINARY: u8 = 0x2;
+pub const OPCODE_CLOSE: u8 = 0x8;
+pub const OPCODE_PING: u8 = 0x9;
+pub const OPCODE_PONG: u8 = 0xA;
+
+pub const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+pub const CLOSE_UNSUPPORTED_DATA: u16 = 1003;
+pub const CLOSE_INVALID_PAYLOAD: u16 = 1007;
+pub const CLOSE_MESSAGE_TOO_BIG: u16 = 1009;
+
+/// A frame received from the client, with its payload unmasked in place.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Frame {
+    pub fin: bool,
+    pub opcode: u8,
+    /// The position of the payload in the buffer.
+    pub payload: Range<usize>,
+    /// The total length of the frame in the buffer.
+    pub len: usize,
+}
+
+/// What to do after a frame was received from the client.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Received<'a> {
+    /// Nothing, as the frame is a pong or does not end a message.
+    Nothing,
+    /// Answer with a pong with the given payload.
+    Ping(&'a [u8]),
+    /// Echo the status code of the client, if any, to complete the closing handshake.
+    Close(&'a [u8]),
+    /// Handle the complete text message.
+    Text(&'a str),
+}
+
+/// A message being assembled from the frames received from the client.
+#[derive(Default)]
+pub struct Message {
+    /// The opcode of the first frame of the message, if a message is in progress.
+    opcode: Option<u8>,
+    data: Vec<u8, MAX_MESSAGE_SIZE>,
+}
+
+impl Message {
+    /// Add a frame with the given payload to the message.
+    /// Returns the status code to close the connection with if the frame is not allowed.
+    pub fn receive<'a>(
+        &'a mut self,
+        frame: &Frame,
+        payload: &'a [u8],
+    ) -> Result<Received<'a>, u16> {
+        // Control frames cannot be fragmented and are handled between the frames of a message
+        if frame.opcode & 0x8 != 0 && (!frame.fin || payload.len() > 125) {
+            return Err(CLOSE_PROTOCOL_ERROR);
+        }
+
+        match (frame.opcode, self.opcode) {
+            (OPCODE_CLOSE, _) => return Ok(Received::Close(&payload[..payload.len().min(2)])),
+            (OPCODE_PING, _) => return Ok(Received::Ping(payload)),
+            (OPCODE_PONG, _) => return Ok(Received::Nothing),
+            (OPCODE_TEXT | OPCODE_BINARY, None) => {
+                self.opcode = Some(frame.opcode);
+                self.data.clear();
+                self.data
+                    .extend_from_slice(payload)
+                    .map_err(|_| CLOSE_MESSAGE_TOO_BIG)?;
+            }
+            (OPCODE_CONTINUATION, Some(_)) => {
+                self.data
+                    .extend_from_slice(payload)
+                    .map_err(|_| CLOSE_MESSAGE_TOO_BIG)?;
+            }
+            _ => return Err(CLOSE_PROTOCOL_ERROR),
+        }
+
+        if !frame.fin {
+            return Ok(Received::Nothing);
+        }
+        if self.opcode.take() != Some(OPCODE_TEXT) {
+            return Err(CLOSE_UNSUPPORTED_DATA);
+        }
+        match core::str::from_utf8(&self.data) {
+            Ok(v) => Ok(Received::Text(v)),
+            Err(_) => Err(CLOSE_INVALID_PAYLOAD),
+        }
+    }
+}
+
+/// Compute the accept key of the handshake from the key of the client.
+pub fn accept_key(key: &str) -> Result<[u8; ACCEPT_KEY_SIZE], ()> {
+    let mut input = Vec::<u8, 64>::new();
+    input.extend_from_slice(key.as_bytes())?;
+    input.extend_from_slice(WEBSOCKET_GUID)?;
+
+    let mut accept = [0u8; ACCEPT_KEY_SIZE];
+    encode_base64(&sha1(&input), &mut accept)?;
+    Ok(accept)
+}
+
+/// Parse the frame at the start of the buffer and unmask its payload.
+/// Returns `None` if the frame is not complete yet, or the status code to close
+/// the connection with if the frame is invalid.
+pub fn parse_frame(buffer: &mut [u8]) -> Result<Option<Frame>, u16> {
+    if buffer.len() < 2 {
+        return Ok(None);
+    }
+
+    // Extensions are not supported, so the reserved bits must be zero
+    if buffer[0] & 0x70 != 0 {
+        return Err(CLOSE_PROTOCOL_ERROR);
+    }
+    let fin = buffer[0] & 0x80 != 0;
+    let opcode = buffer[0] & 0x0F;
+
+    // Frames from the client must be masked
+    if buffer[1] & 0x80 == 0 {
+        return Err(CLOSE_PROTOCOL_ERROR);
+    }
+
+    let (payload_len, header_len) = match buffer[1] & 0x7F {
+        126 if buffer.len() >= 4 => (u16::from_be_bytes([buffer[2], buffer[3]]) as u64, 4),
+        127 if buffer.len() >= 10 => {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&buffer[2..10]);
+            (u64::from_be_bytes(bytes), 10)
+        }
+        126 | 127 => return Ok(None),
+        v => (v as u64, 2),
+    };
+    if payload_len > MAX_MESSAGE_SIZE as u64 {
+        return Err(CLOSE_MESSAGE_TOO_BIG);
+    }
+
+    let start = header_len + 4;
+    let end = start + payload_len as usize;
+    if buffer.len() < end {
+        return Ok(None);
+    }
+
+    let mut mask = [0u8; 4];
+    mask.copy_from_slice(&buffer[header_len..start]);
+    buffer[start..end]
+        .iter_mut()
+        .zip(mask.iter().cycle())
+        .for_each(|(v, m)| *v ^= m);
+
+    Ok(Some(Frame {
+        fin,
+        opcode,
+        payload: start..end,
+        len: end,
+    }))
+}
+
+/// Build the header of an unmasked frame with the given opcode and payload length.
+/// Returns the header and its length, or `None` if the payload needs a 64-bit length.
+pub fn frame_header(opcode: u8, payload_len: usize) -> Option<([u8; 4], usize)> {
+    let mut header = [0x80 | opcode, 0, 0, 0];
+    match payload_len {
+        0..=125 => {
+            header[1] = payload_len as u8;
+            Some((header, 2))
+        }
+        len => {
+            let len = u16::try_from(len).ok()?;
+            header[1] = 126;
+            header[2..4].copy_from_slice(&len.to_be_bytes());
+            Some((header, 4))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MASK: [u8; 4] = [0x37, 0xfa, 0x21, 0x3d];
+
+    /// Build a masked frame from the client with the given first byte, length field and payload.
+    fn masked(first: u8, len_field: &[u8], payload: &[u8]) -> std::vec::Vec<u8> {
+        let mut frame = vec![first, 0x80 | len_field[0]];
+        frame.extend_from_slice(&len_field[1..]);
+        frame.extend_from_slice(&MASK);
+        frame.extend(payload.iter().zip(MASK.iter().cycle()).map(|(v, m)| v ^ m));
+        frame
+    }
+
+    /// Parse a complete frame and return it with its unmasked payload.
+    fn parse(buffer: &mut [u8]) -> (Frame, std::vec::Vec<u8>) {
+        let frame = parse_frame(buffer).unwrap().unwrap();
+        let payload = buffer[frame.payload.clone()].to_vec();
+        (frame, payload)
+    }
+
+    /// Build a frame received from the client without a buffer.
+    fn frame(fin: bool, opcode: u8, len: usize) -> Frame {
+        Frame {
+            fin,
+            opcode,
+            payload: 0..len,
+            len,
+        }
+    }
+
+    #[test]
+    fn computes_accept_key() {
+        // The example of RFC 6455 section 1.3
+        let accept = accept_key("dGhlIHNhbXBsZSBub25jZQ==").unwrap();
+        assert_eq!(&accept, b"s3pPLMBiTxaQ9kYGzzhZRbK+xOo=");
+    }
+
+    #[test]
+    fn parses_masked_frame() {
+        // The masked "Hello" of RFC 6455 section 5.7
+        let mut buffer = [
+            0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58,
+        ];
+        let (frame, payload) = parse(&mut buffer);
+        assert!(frame.fin);
+        assert_eq!(frame.opcode, OPCODE_TEXT);
+        assert_eq!(frame.len, buffer.len());
+        assert_eq!(payload, b"Hello");
+    }
+
+    #[test]
+    fn rejects_unmasked_frame() {
+        let mut buffer = [0x81, 0x05, 0x48, 0x65, 0x6c, 0x6c, 0x6f];
+        assert_eq!(parse_frame(&mut buffer), Err(CLOSE_PROTOCOL_ERROR));
+    }
+
+    #[test]
+    fn rejects_reserved_bits() {
+        let mut buffer = masked(0xC1, &[1], b"a");
+        assert_eq!(parse_frame(&mut buffer), Err(CLOSE_PROTOCOL_ERROR));
+    }
+
+    #[test]
+    fn waits_for_complete_frame() {
+        let buffer = masked(0x81, &[126, 0, 200], &[b'a'; 200]);
+        for len in [0, 1, 3, 7, buffer.len() - 1] {
+            let mut partial = buffer[..len].to_vec();
+            assert_eq!(parse_frame(&mut partial), Ok(None), "{}", len);
+        }
+    }
+
+    #[test]
+    fn parses_16_bit_length() {
+        let mut buffer = masked(0x82, &[126, 0, 200], &[b'a'; 200]);
+        let (frame, payload) = parse(&mut buffer);
+        assert_eq!(frame.payload, 8..208);
+        assert_eq!(payload, [b'a'; 200]);
+    }
+
+    #[test]
+    fn parses_64_bit_length() {
+        let mut buffer = masked(0x81, &[127, 0, 0, 0, 0, 0, 0, 0, 3], b"abc");
+        let (frame, payload) = parse(&mut buffer);
+        assert_eq!(frame.payload, 14..17);
+        assert_eq!(payload, b"abc");
+
+        // A partial 64-bit length is not complete yet
+        assert_eq!(parse_frame(&mut buffer[..9]), Ok(None));
+    }
+
+    #[test]
+    fn rejects_oversized_frames() {
+        let len = (MAX_MESSAGE_SIZE as u16 + 1).to_be_bytes();
+        let mut buffer = masked(0x81, &[126, len[0], len[1]], &[]);
+        assert_eq!(parse_frame(&mut buffer), Err(CLOSE_MESSAGE_TOO_BIG));
+
+        let mut buffer = masked(0x81, &[127, 0x80, 0, 0, 0, 0, 0, 0, 0], &[]);
+        assert_eq!(parse_frame(&mut buffer), Err(CLOSE_MESSAGE_TOO_BIG));
+    }
+
+    #[test]
+    fn parses_consecutive_frames() {
+        let mut buffer = masked(0x01, &[3], b"abc");
+        buffer.extend(masked(0x80, &[2], b"de"));
+        let (first, _) = parse(&mut buffer);
+        assert!(!first.fin);
+        assert_eq!(first.opcode, OPCODE_TEXT);
+
+        let (second, payload) = parse(&mut buffer[first.len..]);
+        assert!(second.fin);
+        assert_eq!(second.opcode, OPCODE_CONTINUATION);
+        assert_eq!(payload, b"de");
+    }
+
+    #[test]
+    fn assembles_fragmented_message() {
+        let mut message = Message::default();
+        assert_eq!(
+            message.receive(&frame(false, OPCODE_TEXT, 7), b"action="),
+            Ok(Received::Nothing)
+        );
+        // Control frames are handled between the fragments
+        assert_eq!(
+            message.receive(&frame(true, OPCODE_PING, 2), b"hi"),
+            Ok(Received::Ping(b"hi"))
+        );
+        assert_eq!(
+            message.receive(&frame(true, OPCODE_PONG, 0), b""),
+            Ok(Received::Nothing)
+        );
+        assert_eq!(
+            message.receive(&frame(true, OPCODE_CONTINUATION, 6), b"status"),
+            Ok(Received::Text("action=status"))
+        );
+
+        // The next message starts from scratch
+        assert_eq!(
+            message.receive(&frame(true, OPCODE_TEXT, 1), b"a"),
+            Ok(Received::Text("a"))
+        );
+    }
+
+    #[test]
+    fn echoes_close_status() {
+        let mut message = Message::default();
+        assert_eq!(
+            message.receive(
+                &frame(true, OPCODE_CLOSE, 5),
+                &[0x03, 0xe8, b'b', b'y', b'e']
+            ),
+            Ok(Received::Close(&[0x03, 0xe8]))
+        );
+        assert_eq!(
+            message.receive(&frame(true, OPCODE_CLOSE, 0), &[]),
+            Ok(Received::Close(&[]))
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_control_frames() {
+        let mut message = Message::default();
+        assert_eq!(
+            message.receive(&frame(false, OPCODE_PING, 0), b""),
+            Err(CLOSE_PROTOCOL_ERROR)
+        );
+        assert_eq!(
+            message.receive(&frame(true, OPCODE_PING, 126), &[0; 126]),
+            Err(CLOSE_PROTOCOL_ERROR)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_fragments() {
+        // A continuation without a message
+        let mut message = Message::default();
+        assert_eq!(
+            message.receive(&frame(true, OPCODE_CONTINUATION, 1), b"a"),
+            Err(CLOSE_PROTOCOL_ERROR)
+        );
+
+        // A new message before the end of the previous one
+        let mut message = Message::default();
+        let _ = message.receive(&frame(false, OPCODE_TEXT, 1), b"a");
+        assert_eq!(
+            message.receive(&frame(true, OPCODE_TEXT, 1), b"b"),
+            Err(CLOSE_PROTOCOL_ERROR)
+        );
+    }
+
+    #[test]
+    fn rejects_oversized_fragmented_message() {
+        let mut message = Message::default();
+        let half = [b'a'; MAX_MESSAGE_SIZE / 2 + 1];
+        let _ = message.receive(&frame(false, OPCODE_TEXT, half.len()), &half);
+        assert_eq!(
+            message.receive(&frame(true, OPCODE_CONTINUATION, half.len()), &half),
+            Err(CLOSE_MESSAGE_TOO_BIG)
+        );
+    }
+
+    #[test]
+    fn rejects_binary_and_invalid_text() {
+        let mut message = Message::default();
+        assert_eq!(
+            message.receive(&frame(true, OPCODE_BINARY, 1), b"a"),
+            Err(CLOSE_UNSUPPORTED_DATA)
+        );
+        assert_eq!(
+            message.receive(&frame(true, OPCODE_TEXT, 2), &[0xc3, 0x28]),
+            Err(CLOSE_INVALID_PAYLOAD)
+        );
+    }
+
+    #[test]
+    fn builds_frame_headers() {
+        assert_eq!(frame_header(OPCODE_TEXT, 5), Some(([0x81, 5, 0, 0], 2)));
+        assert_eq!(frame_header(OPCODE_PING, 0), Some(([0x89, 0, 0, 0], 2)));
+        assert_eq!(
+            frame_header(OPCODE_TEXT, 300),
+            Some(([0x81, 126, 0x01, 0x2c], 4))
+        );
+        assert_eq!(frame_header(OPCODE_TEXT, 0x10000), None);
+    }
+}
@@ -1,10 +1,26 @@
-use crate::utils::{convert_mac_address, parse_ip_address};
+use super::{
+    CommandError, api, command_response,
+    form::Form,
+    html_responses,
+    request::Method,
+    response::Response,
+    router::{Feature, Route},
+    status::StatusCode,
+};
+use crate::{
+    events::{Event, publish_event},
+    metrics::update_metrics,
+    utils::parse_ip_address,
+};
+use alloc::boxed::Box;
 use embassy_net::{
     IpAddress, IpEndpoint, Stack,
     udp::{PacketMetadata, UdpSocket},
 };
 use embassy_time::{Duration, Timer};
 
+/// The enable flag for the WOL feature.
+pub const WOL_ENABLE: &str = env!("WOL_ENABLE");
 /// The port on which the device will listen for UDP requests.
 const UDP_BIND_PORT: u16 = 9;
 /// The buffer size for the UDP socket.
@@ -15,21 +31,52 @@ const WOL_BROADCAST_ADDR: &str = env!("WOL_BROADCAST_ADDR");
 /// The fallback broadcast address to send the WOL packet to.
 const WOL_BROADCAST_ADDR_FALLBACK: IpAddress = IpAddress::v4(255, 255, 255, 255);
 
-/// Send a Wake-on-LAN command to the specified MAC address.
-pub async fn wol_command(stack: Stack<'_>, mac_addr: &str) -> Result<(), ()> {
-    // Replace "%3A" with ":" in the MAC address
-    let mac_addr = match convert_mac_address(mac_addr) {
-        Ok(v) => v,
-        Err(_) => {
-            log::error!("WOL | Error parsing MAC address");
-            return Err(());
-        }
+/// The routes of the WOL feature.
+pub const ROUTES: &[Route] = &[
+    Route {
+        path: "/wol",
+        methods: &[Method::Get, Method::Post],
+        handler: |cx| Box::pin(wol_page(cx.stack, cx.args)),
+        feature: Some(Feature::Wol),
+        menu_label: Some("WOL"),
+    },
+    Route {
+        path: "/api/v1/wol",
+        methods: &[Method::Get, Method::Post],
+        handler: |cx| Box::pin(wol_api(cx.stack, cx.args)),
+        feature: Some(Feature::Wol),
+        menu_label: None,
+    },
+];
+
+/// Handle the WOL page of the web interface.
+pub async fn wol_page(stack: Stack<'_>, args: &Form) -> Response {
+    match args.get("mac_addr") {
+        Some(v) => command_response(wol_command(stack, v).await, html_responses::WOL_SUCCESS),
+        None => Response::html(StatusCode::Ok, html_responses::WOL_INPUT),
+    }
+}
+
+/// Handle the WOL endpoint of the JSON API.
+pub async fn wol_api(stack: Stack<'_>, args: &Form) -> Response {
+    let Some(mac_addr) = args.get("mac_addr") else {
+        return api::error(StatusCode::BadRequest, "Missing parameter 'mac_addr'");
     };
-    let wol_packet = match generate_wol_packet(mac_addr.as_str()) {
+    api::command_response(
+        wol_command(stack, mac_addr).await,
+        "Invalid MAC address",
+        "Could not send WOL packet",
+    )
+}
+
+/// Send a Wake-on-LAN command to the specified MAC address.
+pub async fn wol_command(stack: Stack<'_>, mac_addr: &str) -> Result<(), CommandError> {
+    let mac_addr = mac_addr.trim();
+    let wol_packet = match generate_wol_packet(mac_addr) {
         Ok(v) => v,
         Err(e) => {
             log::warn!("WOL | Error creating WOL packet -> {}: \"{}\"", e, mac_addr);
-            return Err(());
+            return Err(CommandError::InvalidInput);
         }
     };
     let wol_target = get_broadcast_addr(WOL_BROADCAST_ADDR);
@@ -50,8 +97,9 @@ pub async fn wol_command(stack: Stack<'_>, mac_addr: &str) -> Result<(), ()> {
 
     if let Err(e) = socket.bind(UDP_BIND_PORT) {
         log::error!("WOL | Error binding UDP socket to port: {:?}", e);
+        update_metrics(|metrics| metrics.wol_failed += 1);
         socket.close();
-        return Err(());
+        return Err(CommandError::Failed);
     }
 
     // Try to send the WOL packet 3 times max
@@ -76,20 +124,28 @@ pub async fn wol_command(stack: Stack<'_>, mac_addr: &str) -> Result<(), ()> {
 
     if i == MAX_TRIES {
         log::error!("WOL | Failed to send WOL packet");
+        update_metrics(|metrics| metrics.wol_failed += 1);
         socket.close();
-        return Err(());
+        return Err(CommandError::Failed);
     }
 
+    update_metrics(|metrics| metrics.wol_sent += 1);
+    let mut mac_bytes = [0u8; 6];
+    mac_bytes.copy_from_slice(&wol_packet[6..12]);
+    publish_event(Event::WolSent {
+        mac_addr: mac_bytes,
+    });
     socket.close();
     Ok(())
 }
 
 /// Create a Wake-on-LAN packet from a MAC address.
+/// The bytes of the MAC address can be separated by `:` or `-`.
 /// The packet is a 102-byte array with the first 6 bytes set to 0xFF and the MAC address repeated 16 times.
 fn generate_wol_packet(mac_addr: &str) -> Result<[u8; 102], &str> {
     // Parse the MAC address
     let mut mac_bytes = [0u8; 6];
-    let mut parts = mac_addr.split(':');
+    let mut parts = mac_addr.split([':', '-']);
     let status = (0..6).try_for_each(|i| {
         let part = match parts.next() {
             Some(v) => v,
@@ -105,6 +161,9 @@ fn generate_wol_packet(mac_addr: &str) -> Result<[u8; 102], &str> {
 
     // Return an error if the MAC address parsing failed
     status?;
+    if parts.next().is_some() {
+        return Err("Invalid MAC address size");
+    }
 
     let mut wol_packet = [0u8; 102];
 
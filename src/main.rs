@@ -2,9 +2,19 @@
 #![no_main]
 #![feature(impl_trait_in_assoc_type)]
 
+extern crate alloc;
+
 mod dns;
+mod events;
+mod flash;
+mod http_client;
 mod http_server;
+mod metrics;
 mod pins;
+mod slaac;
+mod state;
+#[cfg(feature = "tls")]
+mod tls;
 mod utils;
 
 use core::str::FromStr;
@@ -29,9 +39,12 @@ use esp_wifi::{
         WifiState,
     },
 };
-use http_server::http_server_task;
+use events::{Event, publish_event};
+use http_server::{get_http_task_count, http_server_task};
 use log::info;
+use metrics::update_metrics;
 use pins::*;
+use state::update_state;
 
 /// The hostname of the device.
 const HOSTNAME: &str = env!("HOSTNAME");
@@ -62,6 +75,9 @@ async fn main(spawner: Spawner) {
     let mut rng = Rng::new(peripherals.RNG);
 
     esp_alloc::heap_allocator!(72 * 1024);
+    // The TLS sessions get their own region, sized for all the sessions that can be open at once
+    #[cfg(feature = "tls")]
+    esp_alloc::heap_allocator!(tls::TLS_HEAP_SIZE);
 
     // Initialize GPIO pins
     let gpio2 = OutputOpenDrain::new(peripherals.GPIO2, Level::High, Pull::Up);
@@ -107,6 +123,8 @@ async fn main(spawner: Spawner) {
         hostname = trimmed_hostname
     }
 
+    update_state(|state| state.hostname = hostname);
+
     // Configure DHCPv4
     let mut dhcp_config = DhcpConfig::default();
     dhcp_config.hostname = Some(heapless::String::from_str(hostname).unwrap());
@@ -117,11 +135,15 @@ async fn main(spawner: Spawner) {
     rng.read(&mut seed_buf);
     let seed: u64 = u64::from_ne_bytes(seed_buf);
 
+    #[cfg(feature = "tls")]
+    tls::init_tls(rng);
+
     // Create the wifi stack
+    // The stack needs enough sockets for DHCP, DNS, SLAAC, the DNS updater and its verification, WOL and the HTTP server tasks
     let (stack, runner) = embassy_net::new(
         wifi_interface,
         config,
-        singleton!(:StackResources<8> = StackResources::new()).unwrap(),
+        singleton!(:StackResources<14> = StackResources::new()).unwrap(),
         seed,
     );
 
@@ -131,10 +153,19 @@ async fn main(spawner: Spawner) {
     spawner.spawn(connection(controller)).ok();
     spawner.spawn(net_task(runner)).ok();
     if DNS_ENABLE == "true" || DNS_ENABLE == "1" {
-        spawner.spawn(dns_updater_task(stack)).ok();
+        spawner.spawn(dns_updater_task(stack, rng)).ok();
+        if dns::ipv6_enabled() {
+            spawner.spawn(slaac::slaac_task(stack)).ok();
+        }
     }
     if HTTP_SERVER_ENABLE == "true" || HTTP_SERVER_ENABLE == "1" {
-        spawner.spawn(http_server_task(stack)).ok();
+        for id in 0..get_http_task_count() {
+            spawner.spawn(http_server_task(stack, id)).ok();
+        }
+        #[cfg(feature = "https")]
+        if http_server::https_redirect_enabled() {
+            spawner.spawn(http_server::http_redirect_task(stack)).ok();
+        }
     }
 }
 
@@ -146,6 +177,8 @@ async fn connection(mut controller: WifiController<'static>) {
         if esp_wifi::wifi::wifi_state() == WifiState::StaConnected {
             // Wait until we're no longer connected
             controller.wait_for_event(WifiEvent::StaDisconnected).await;
+            update_metrics(|metrics| metrics.wifi_disconnects += 1);
+            publish_event(Event::WifiDisconnected);
             Timer::after(Duration::from_millis(5000)).await
         }
 
@@ -163,7 +196,10 @@ async fn connection(mut controller: WifiController<'static>) {
         log::info!("SYS | About to connect...");
 
         match controller.connect() {
-            Ok(_) => log::info!("SYS | Wifi connected!"),
+            Ok(_) => {
+                log::info!("SYS | Wifi connected!");
+                publish_event(Event::WifiConnected);
+            }
             Err(e) => {
                 log::error!("SYS | Failed to connect to wifi: {e:?}");
                 Timer::after(Duration::from_millis(5000)).await
@@ -0,0 +1,61 @@
+use core::cell::RefCell;
+use embassy_sync::blocking_mutex::{CriticalSectionMutex, Mutex, raw::CriticalSectionRawMutex};
+use embassy_time::Instant;
+use heapless::FnvIndexMap;
+
+/// The first GPIO pin that can be used as a switch.
+pub const FIRST_SWITCH_PIN: u8 = 2;
+/// The number of GPIO pins that can be used as a switch.
+pub const SWITCH_PIN_COUNT: usize = 8;
+/// The maximum number of route and status pairs tracked for HTTP requests.
+pub const MAX_HTTP_SERIES: usize = 32;
+
+/// The counters and gauges collected by the device.
+#[derive(Clone)]
+pub struct Metrics {
+    pub wol_sent: u32,
+    pub wol_failed: u32,
+    /// The number of pulses of each switch pin, starting at `FIRST_SWITCH_PIN`.
+    pub switch_pulses: [u32; SWITCH_PIN_COUNT],
+    pub dns_update_successes: u32,
+    pub dns_update_failures: u32,
+    /// When the published public IP address last changed.
+    pub dns_last_change: Option<Instant>,
+    /// The number of times the Wi-Fi connection was lost.
+    pub wifi_disconnects: u32,
+    /// The number of HTTP requests for each route and status code.
+    pub http_requests: FnvIndexMap<(&'static str, u16), u32, MAX_HTTP_SERIES>,
+}
+
+pub static METRICS: Mutex<CriticalSectionRawMutex, RefCell<Metrics>> =
+    CriticalSectionMutex::new(RefCell::new(Metrics {
+        wol_sent: 0,
+        wol_failed: 0,
+        switch_pulses: [0; SWITCH_PIN_COUNT],
+        dns_update_successes: 0,
+        dns_update_failures: 0,
+        dns_last_change: None,
+        wifi_disconnects: 0,
+        http_requests: FnvIndexMap::new(),
+    }));
+
+/// Update the metrics of the device.
+pub fn update_metrics(f: impl FnOnce(&mut Metrics)) {
+    METRICS.lock(|metrics| f(&mut metrics.borrow_mut()));
+}
+
+/// Read the metrics of the device.
+pub fn read_metrics<R>(f: impl FnOnce(&Metrics) -> R) -> R {
+    METRICS.lock(|metrics| f(&metrics.borrow()))
+}
+
+/// Count an HTTP request served on the given route with the given status code.
+pub fn record_http_request(route: &'static str, status: u16) {
+    update_metrics(|metrics| {
+        if let Some(count) = metrics.http_requests.get_mut(&(route, status)) {
+            *count += 1;
+        } else if metrics.http_requests.insert((route, status), 1).is_err() {
+            log::warn!("SYS | Too many HTTP request series to track");
+        }
+    });
+}
@@ -0,0 +1,259 @@
+use core::net::Ipv6Addr;
+use embassy_net::{
+    ConfigV6, HardwareAddress, Ipv6Cidr, Stack, StaticConfigV6,
+    raw::{IpProtocol, IpVersion, PacketMetadata, RawSocket},
+};
+use embassy_time::{Duration, Instant, Timer, with_deadline};
+use esp_wifi::wifi::{WifiDevice, WifiStaDevice};
+use heapless::Vec;
+
+/// The buffer size for the ICMPv6 packets, which is enough for router advertisements with a few options.
+const PACKET_BUFFER_SIZE: usize = 512;
+/// The time in seconds to wait for a router advertisement after a router solicitation.
+const ADVERTISEMENT_TIMEOUT: u64 = 5;
+/// The time in seconds between the router solicitations when no router answers.
+const RETRY_DELAY: u64 = 30;
+/// The minimum and maximum time in seconds between the router solicitations once an address is configured.
+const REFRESH_DELAY_MIN: u64 = 60;
+const REFRESH_DELAY_MAX: u64 = 3600;
+
+/// The multicast address of all the routers of the link.
+const ALL_ROUTERS: Ipv6Addr = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 2);
+/// The ICMPv6 type of a router solicitation.
+const ROUTER_SOLICITATION: u8 = 133;
+/// The ICMPv6 type of a router advertisement.
+const ROUTER_ADVERTISEMENT: u8 = 134;
+/// The option type of the link-layer address of the sender.
+const SOURCE_LINK_LAYER_OPTION: u8 = 1;
+/// The option type of a prefix of the link.
+const PREFIX_INFORMATION_OPTION: u8 = 3;
+/// The flag of a prefix that can be used to configure an address.
+const AUTONOMOUS_FLAG: u8 = 0x40;
+/// The hop limit of the neighbor discovery messages, which proves that they come from the link.
+const HOP_LIMIT: u8 = 255;
+/// The size of the IPv6 header.
+const IPV6_HEADER_SIZE: usize = 40;
+
+/// The information of a router advertisement needed to configure a global address.
+struct RouterAdvertisement {
+    /// The link-local address of the router.
+    router: Ipv6Addr,
+    /// Whether the router can be used as the default gateway.
+    is_default: bool,
+    /// The /64 prefix of the global address.
+    prefix: Ipv6Addr,
+    /// The time in seconds during which the prefix is valid.
+    valid_lifetime: u32,
+}
+
+/// The embassy task that configures the global IPv6 address of the device with SLAAC.
+/// The prefix is taken from the router advertisements and the interface ID from the MAC address.
+#[embassy_executor::task]
+pub async fn slaac_task(stack: Stack<'static>) {
+    log::info!("SYS | Started SLAAC task");
+    let HardwareAddress::Ethernet(mac) = stack.hardware_address();
+    let mac = mac.0;
+    let link_local = with_interface_id(Ipv6Addr::new(0xfe80, 0, 0, 0, 0, 0, 0, 0), mac);
+
+    let mut rx_meta = [PacketMetadata::EMPTY; 2];
+    let mut rx_buffer = [0; PACKET_BUFFER_SIZE];
+    let mut tx_meta = [PacketMetadata::EMPTY; 1];
+    let mut tx_buffer = [0; PACKET_BUFFER_SIZE];
+    let socket = RawSocket::new::<WifiDevice<'static, WifiStaDevice>>(
+        stack,
+        IpVersion::Ipv6,
+        IpProtocol::Icmpv6,
+        &mut rx_meta,
+        &mut rx_buffer,
+        &mut tx_meta,
+        &mut tx_buffer,
+    );
+
+    let mut expiration: Option<Instant> = None;
+    loop {
+        while !stack.is_link_up() {
+            Timer::after(Duration::from_millis(500)).await;
+        }
+
+        // The router solicitation needs a source address, so start with the link-local one
+        let source = match stack.config_v6() {
+            Some(v) => v.address.address(),
+            None => {
+                set_address(stack, link_local, None);
+                link_local
+            }
+        };
+
+        let delay = match solicit_router(&socket, source, mac).await {
+            Some(advertisement) => {
+                let address = with_interface_id(advertisement.prefix, mac);
+                let gateway = advertisement.is_default.then_some(advertisement.router);
+                let current = stack.config_v6();
+                if current.as_ref().map(|v| v.address.address()) != Some(address)
+                    || current.and_then(|v| v.gateway) != gateway
+                {
+                    log::info!("SYS | Device IPv6: {}", address);
+                    set_address(stack, address, gateway);
+                }
+                let valid_lifetime = advertisement.valid_lifetime as u64;
+                expiration = Some(Instant::now() + Duration::from_secs(valid_lifetime));
+                (valid_lifetime / 2).clamp(REFRESH_DELAY_MIN, REFRESH_DELAY_MAX)
+            }
+            None => {
+                if expiration.is_some_and(|v| v <= Instant::now()) {
+                    log::warn!("SYS | IPv6 prefix expired without a new router advertisement");
+                    set_address(stack, link_local, None);
+                    expiration = None;
+                }
+                RETRY_DELAY
+            }
+        };
+        Timer::after(Duration::from_secs(delay)).await;
+    }
+}
+
+/// Set the IPv6 address of the device, replacing the previous one.
+fn set_address(stack: Stack<'_>, address: Ipv6Addr, gateway: Option<Ipv6Addr>) {
+    stack.set_config_v6(ConfigV6::Static(StaticConfigV6 {
+        address: Ipv6Cidr::new(address, 64),
+        gateway,
+        dns_servers: Vec::new(),
+    }));
+}
+
+/// Send a router solicitation and wait for an advertisement with a prefix usable for a global address.
+async fn solicit_router(
+    socket: &RawSocket<'_>,
+    source: Ipv6Addr,
+    mac: [u8; 6],
+) -> Option<RouterAdvertisement> {
+    // The link-layer address option lets the router answer without resolving the address of the device
+    let mut packet = [0u8; IPV6_HEADER_SIZE + 16];
+    write_ipv6_header(&mut packet, source, ALL_ROUTERS, 16);
+    let message = &mut packet[IPV6_HEADER_SIZE..];
+    message[0] = ROUTER_SOLICITATION;
+    message[8] = SOURCE_LINK_LAYER_OPTION;
+    message[9] = 1;
+    message[10..16].copy_from_slice(&mac);
+    let checksum = icmpv6_checksum(source, ALL_ROUTERS, message);
+    message[2..4].copy_from_slice(&checksum.to_be_bytes());
+    socket.send(&packet).await;
+
+    let deadline = Instant::now() + Duration::from_secs(ADVERTISEMENT_TIMEOUT);
+    let mut buf = [0u8; PACKET_BUFFER_SIZE];
+    loop {
+        let len = match with_deadline(deadline, socket.recv(&mut buf)).await {
+            Ok(Ok(v)) => v,
+            Ok(Err(_)) => continue,
+            Err(_) => {
+                log::warn!("SYS | No IPv6 router advertisement received");
+                return None;
+            }
+        };
+        if let Some(v) = parse_router_advertisement(&buf[..len]) {
+            return Some(v);
+        }
+    }
+}
+
+/// Parse a router advertisement and find its first prefix usable for a global address.
+/// Returns `None` if the packet is another ICMPv6 message or if it has no such prefix.
+fn parse_router_advertisement(packet: &[u8]) -> Option<RouterAdvertisement> {
+    if packet.len() < IPV6_HEADER_SIZE + 16 || packet[7] != HOP_LIMIT {
+        return None;
+    }
+    let source = ipv6_at(packet, 8);
+    let destination = ipv6_at(packet, 24);
+    let message = &packet[IPV6_HEADER_SIZE..];
+    if message[0] != ROUTER_ADVERTISEMENT
+        || source.segments()[0] & 0xffc0 != 0xfe80
+        || icmpv6_checksum(source, destination, message) != 0
+    {
+        return None;
+    }
+
+    let router_lifetime = u16::from_be_bytes([message[6], message[7]]);
+    let mut options = &message[16..];
+    while options.len() >= 8 {
+        let len = options[1] as usize * 8;
+        if len == 0 || len > options.len() {
+            return None;
+        }
+        let (option, rest) = options.split_at(len);
+        options = rest;
+
+        if option[0] != PREFIX_INFORMATION_OPTION || len != 32 {
+            continue;
+        }
+        let valid_lifetime = u32::from_be_bytes([option[4], option[5], option[6], option[7]]);
+        let prefix = ipv6_at(option, 16);
+        // Only global unicast prefixes of 64 bits can be completed with the interface ID
+        if option[2] == 64
+            && option[3] & AUTONOMOUS_FLAG != 0
+            && valid_lifetime > 0
+            && prefix.segments()[0] & 0xe000 == 0x2000
+        {
+            return Some(RouterAdvertisement {
+                router: source,
+                is_default: router_lifetime > 0,
+                prefix,
+                valid_lifetime,
+            });
+        }
+    }
+    None
+}
+
+/// Write an IPv6 header for an ICMPv6 neighbor discovery message of `payload_len` bytes.
+fn write_ipv6_header(packet: &mut [u8], source: Ipv6Addr, destination: Ipv6Addr, payload_len: u16) {
+    packet[0] = 0x60;
+    packet[4..6].copy_from_slice(&payload_len.to_be_bytes());
+    packet[6] = IpProtocol::Icmpv6.into();
+    packet[7] = HOP_LIMIT;
+    packet[8..24].copy_from_slice(&source.octets());
+    packet[24..40].copy_from_slice(&destination.octets());
+}
+
+/// Compute the checksum of an ICMPv6 message, which covers a pseudo-header made of the addresses.
+/// The checksum of a received message with a valid checksum is 0.
+fn icmpv6_checksum(source: Ipv6Addr, destination: Ipv6Addr, message: &[u8]) -> u16 {
+    let mut sum: u32 = 0;
+    let mut add = |bytes: &[u8]| {
+        for chunk in bytes.chunks(2) {
+            sum += u16::from_be_bytes([chunk[0], *chunk.get(1).unwrap_or(&0)]) as u32;
+        }
+    };
+    add(&source.octets());
+    add(&destination.octets());
+    add(&(message.len() as u32).to_be_bytes());
+    add(&[0, 0, 0, IpProtocol::Icmpv6.into()]);
+    add(message);
+
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Read an IPv6 address at index `start` of the packet.
+fn ipv6_at(packet: &[u8], start: usize) -> Ipv6Addr {
+    let mut octets = [0u8; 16];
+    octets.copy_from_slice(&packet[start..start + 16]);
+    Ipv6Addr::from(octets)
+}
+
+/// Replace the last 64 bits of the prefix with the modified EUI-64 interface ID of the MAC address.
+fn with_interface_id(prefix: Ipv6Addr, mac: [u8; 6]) -> Ipv6Addr {
+    let mut octets = prefix.octets();
+    octets[8..].copy_from_slice(&[
+        mac[0] ^ 0x02,
+        mac[1],
+        mac[2],
+        0xff,
+        0xfe,
+        mac[3],
+        mac[4],
+        mac[5],
+    ]);
+    Ipv6Addr::from(octets)
+}
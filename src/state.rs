@@ -0,0 +1,54 @@
+use core::{cell::RefCell, net::Ipv6Addr};
+use embassy_net::IpAddress;
+use embassy_sync::blocking_mutex::{CriticalSectionMutex, Mutex, raw::CriticalSectionRawMutex};
+use embassy_time::Instant;
+
+/// The result of the last DNS update.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DnsUpdate {
+    /// When the update was attempted.
+    pub time: Instant,
+    /// Whether the DNS provider was successfully updated.
+    pub success: bool,
+    /// The result given by the DNS provider, or the reason of the failure.
+    pub message: &'static str,
+    /// Whether the updates are stopped until the configuration changes, after a fatal error.
+    pub stopped: bool,
+}
+
+/// The state of the device shared between the tasks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceState {
+    /// The hostname used by the device on the network.
+    pub hostname: &'static str,
+    /// The last public IP address found by the DNS updater.
+    pub public_ip: Option<IpAddress>,
+    /// The last public IPv6 address found by the DNS updater.
+    pub public_ipv6: Option<Ipv6Addr>,
+    /// The result of the last DNS update.
+    pub dns_update: Option<DnsUpdate>,
+    /// Whether the hostname resolved to the published IPv4 address at the last verification.
+    pub dns_verified: Option<bool>,
+    /// Whether the hostname resolved to the published IPv6 address at the last verification.
+    pub dns_verified_ipv6: Option<bool>,
+}
+
+pub static DEVICE_STATE: Mutex<CriticalSectionRawMutex, RefCell<DeviceState>> =
+    CriticalSectionMutex::new(RefCell::new(DeviceState {
+        hostname: "",
+        public_ip: None,
+        public_ipv6: None,
+        dns_update: None,
+        dns_verified: None,
+        dns_verified_ipv6: None,
+    }));
+
+/// Get a copy of the current state of the device.
+pub fn get_state() -> DeviceState {
+    DEVICE_STATE.lock(|state| *state.borrow())
+}
+
+/// Update the state of the device.
+pub fn update_state(f: impl FnOnce(&mut DeviceState)) {
+    DEVICE_STATE.lock(|state| f(&mut state.borrow_mut()));
+}
@@ -0,0 +1,424 @@
+use alloc::boxed::Box;
+use core::{cell::RefCell, convert::Infallible, ffi::CStr};
+use embassy_net::tcp::{TcpReader, TcpSocket, TcpWriter};
+use embassy_sync::blocking_mutex::{CriticalSectionMutex, Mutex, raw::CriticalSectionRawMutex};
+use esp_hal::{riscv::singleton, rng::Rng};
+use heapless::{String, Vec};
+use mbedtls_rs::{
+    AuthMode, Certificate, ClientSessionConfig, Session, SessionConfig, Split, Tls, TlsReference,
+    TlsVersion, X509,
+    io::{ErrorKind, ErrorType, Read, Write},
+    sys::{
+        mbedtls_sha256_context, mbedtls_sha256_finish, mbedtls_sha256_free, mbedtls_sha256_init,
+        mbedtls_sha256_starts, mbedtls_sha256_update,
+    },
+};
+use rand_core::{TryCryptoRng, TryRng};
+
+/// The heap used by a TLS session for its records and handshake.
+const TLS_SESSION_HEAP_SIZE: usize = 40 * 1024;
+/// The number of TLS sessions that can be open at the same time.
+/// The DNS updater needs one, and the HTTPS server tasks one each, but the memory left
+/// next to the tasks of the server only fits a few sessions.
+#[cfg(feature = "https")]
+pub const MAX_TLS_SESSIONS: usize = 3;
+/// The number of TLS sessions that can be open at the same time, which is the one of the DNS updater.
+#[cfg(not(feature = "https"))]
+pub const MAX_TLS_SESSIONS: usize = 1;
+/// The heap reserved for the TLS sessions.
+pub const TLS_HEAP_SIZE: usize = TLS_SESSION_HEAP_SIZE * MAX_TLS_SESSIONS;
+/// The maximum length of the name of a server, including the null byte.
+const SERVER_NAME_SIZE: usize = 128;
+/// The content type of the TLS records carrying handshake messages.
+const RECORD_HANDSHAKE: u8 = 22;
+/// The type of the handshake message carrying the certificates of the server.
+const MESSAGE_CERTIFICATE: u8 = 11;
+
+/// The reference to the TLS library, available once it is initialized.
+static TLS: Mutex<CriticalSectionRawMutex, RefCell<Option<TlsReference<'static>>>> =
+    CriticalSectionMutex::new(RefCell::new(None));
+
+/// The random number generator used by the TLS library, backed by the hardware RNG.
+/// The hardware RNG is a true random number generator while the radio is enabled.
+struct TlsRng(Rng);
+
+impl TryRng for TlsRng {
+    type Error = Infallible;
+
+    fn try_next_u32(&mut self) -> Result<u32, Self::Error> {
+        Ok(self.0.random())
+    }
+
+    fn try_next_u64(&mut self) -> Result<u64, Self::Error> {
+        Ok(((self.0.random() as u64) << 32) | self.0.random() as u64)
+    }
+
+    fn try_fill_bytes(&mut self, dst: &mut [u8]) -> Result<(), Self::Error> {
+        self.0.read(dst);
+        Ok(())
+    }
+}
+
+impl TryCryptoRng for TlsRng {}
+
+/// How the TLS client verifies the certificate of the server.
+pub enum Verification<'a> {
+    /// The certificate must be signed by one of the certificate authorities, in the PEM format.
+    CaChain(&'a CStr),
+    /// The SHA-256 fingerprint of the certificate must match.
+    /// The certificate is only visible before the encryption starts with TLS 1.2.
+    Fingerprint([u8; 32]),
+}
+
+/// A TCP socket used as the transport of a TLS session.
+pub struct TcpStream<'a, 's> {
+    pub socket: &'a mut TcpSocket<'s>,
+    /// The fingerprint of the certificate of the server, computed while reading the handshake.
+    /// It is boxed to keep the stream small once the handshake is over.
+    fingerprint: Option<Box<CertificateFingerprint>>,
+}
+
+impl<'a, 's> TcpStream<'a, 's> {
+    pub fn new(socket: &'a mut TcpSocket<'s>) -> Self {
+        Self {
+            socket,
+            fingerprint: None,
+        }
+    }
+}
+
+impl ErrorType for TcpStream<'_, '_> {
+    type Error = ErrorKind;
+}
+
+impl Read for TcpStream<'_, '_> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        let n = self
+            .socket
+            .read(buf)
+            .await
+            .map_err(|_| ErrorKind::ConnectionReset)?;
+        if let Some(fingerprint) = self.fingerprint.as_mut() {
+            fingerprint.update(&buf[..n]);
+        }
+        Ok(n)
+    }
+}
+
+impl Write for TcpStream<'_, '_> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.socket
+            .write(buf)
+            .await
+            .map_err(|_| ErrorKind::ConnectionReset)
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.socket
+            .flush()
+            .await
+            .map_err(|_| ErrorKind::ConnectionReset)
+    }
+}
+
+impl Split for TcpStream<'_, '_> {
+    type Read<'a>
+        = TcpStreamReader<'a>
+    where
+        Self: 'a;
+    type Write<'a>
+        = TcpStreamWriter<'a>
+    where
+        Self: 'a;
+
+    fn split(&mut self) -> (Self::Read<'_>, Self::Write<'_>) {
+        let (reader, writer) = self.socket.split();
+        (TcpStreamReader(reader), TcpStreamWriter(writer))
+    }
+}
+
+/// The read half of a TCP stream, used by the read half of a TLS session.
+/// The fingerprint is not computed, as a session is only split after its handshake.
+pub struct TcpStreamReader<'a>(TcpReader<'a>);
+
+impl ErrorType for TcpStreamReader<'_> {
+    type Error = ErrorKind;
+}
+
+impl Read for TcpStreamReader<'_> {
+    async fn read(&mut self, buf: &mut [u8]) -> Result<usize, Self::Error> {
+        self.0
+            .read(buf)
+            .await
+            .map_err(|_| ErrorKind::ConnectionReset)
+    }
+}
+
+/// The write half of a TCP stream, used by the write half of a TLS session.
+pub struct TcpStreamWriter<'a>(TcpWriter<'a>);
+
+impl ErrorType for TcpStreamWriter<'_> {
+    type Error = ErrorKind;
+}
+
+impl Write for TcpStreamWriter<'_> {
+    async fn write(&mut self, buf: &[u8]) -> Result<usize, Self::Error> {
+        self.0
+            .write(buf)
+            .await
+            .map_err(|_| ErrorKind::ConnectionReset)
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        self.0.flush().await.map_err(|_| ErrorKind::ConnectionReset)
+    }
+}
+
+/// Computes the SHA-256 fingerprint of the certificate of a TLS 1.2 server
+/// from the handshake records read from the socket, which are sent before the encryption starts.
+struct CertificateFingerprint {
+    record_header: Vec<u8, 5>,
+    /// The number of bytes left in the current record.
+    record_left: usize,
+    message_header: Vec<u8, 4>,
+    message_type: u8,
+    /// The number of bytes left in the current handshake message.
+    message_left: usize,
+    /// The position in the body of the certificate message.
+    position: usize,
+    certificate_len: usize,
+    sha256: mbedtls_sha256_context,
+    fingerprint: Option<[u8; 32]>,
+    /// Set once the fingerprint is computed or the handshake is encrypted.
+    done: bool,
+}
+
+impl CertificateFingerprint {
+    fn new() -> Self {
+        // SAFETY: The context is a plain C struct that is initialized by `mbedtls_sha256_init`
+        let mut sha256: mbedtls_sha256_context = unsafe { core::mem::zeroed() };
+        // SAFETY: The context is valid and freed when the fingerprint is dropped
+        unsafe {
+            mbedtls_sha256_init(&mut sha256);
+            mbedtls_sha256_starts(&mut sha256, 0);
+        }
+        Self {
+            record_header: Vec::new(),
+            record_left: 0,
+            message_header: Vec::new(),
+            message_type: 0,
+            message_left: 0,
+            position: 0,
+            certificate_len: 0,
+            sha256,
+            fingerprint: None,
+            done: false,
+        }
+    }
+
+    /// Read the bytes received from the server, split into TLS records.
+    fn update(&mut self, mut data: &[u8]) {
+        while !data.is_empty() && !self.done {
+            if self.record_left == 0 {
+                let n = data.len().min(5 - self.record_header.len());
+                let _ = self.record_header.extend_from_slice(&data[..n]);
+                data = &data[n..];
+                if let [content_type, _, _, len_high, len_low] = self.record_header[..] {
+                    // Any other record means that the handshake is over or encrypted
+                    self.done = content_type != RECORD_HANDSHAKE;
+                    self.record_left = u16::from_be_bytes([len_high, len_low]) as usize;
+                    self.record_header.clear();
+                }
+                continue;
+            }
+
+            let n = data.len().min(self.record_left);
+            self.update_handshake(&data[..n]);
+            self.record_left -= n;
+            data = &data[n..];
+        }
+    }
+
+    /// Read the content of the handshake records, split into handshake messages.
+    fn update_handshake(&mut self, mut data: &[u8]) {
+        while !data.is_empty() && !self.done {
+            if self.message_left == 0 {
+                let n = data.len().min(4 - self.message_header.len());
+                let _ = self.message_header.extend_from_slice(&data[..n]);
+                data = &data[n..];
+                if let [message_type, a, b, c] = self.message_header[..] {
+                    self.message_type = message_type;
+                    self.message_left = u32::from_be_bytes([0, a, b, c]) as usize;
+                    self.position = 0;
+                    self.message_header.clear();
+                }
+                continue;
+            }
+
+            let n = data.len().min(self.message_left);
+            if self.message_type == MESSAGE_CERTIFICATE {
+                self.update_certificate(&data[..n]);
+            }
+            self.message_left -= n;
+            data = &data[n..];
+        }
+    }
+
+    /// Hash the first certificate of the certificate message, which is the one of the server.
+    /// The body of the message starts with the length of the list and the length of the first certificate.
+    fn update_certificate(&mut self, mut data: &[u8]) {
+        while !data.is_empty() && !self.done {
+            if self.position < 6 {
+                if self.position >= 3 {
+                    self.certificate_len = (self.certificate_len << 8) | data[0] as usize;
+                }
+                self.position += 1;
+                data = &data[1..];
+                continue;
+            }
+
+            let n = data.len().min(6 + self.certificate_len - self.position);
+            // SAFETY: The context is initialized and the input is valid for `n` bytes
+            unsafe { mbedtls_sha256_update(&mut self.sha256, data.as_ptr(), n) };
+            self.position += n;
+            data = &data[n..];
+
+            if self.position == 6 + self.certificate_len {
+                let mut fingerprint = [0u8; 32];
+                // SAFETY: The context is initialized and the output is 32 bytes long
+                unsafe { mbedtls_sha256_finish(&mut self.sha256, fingerprint.as_mut_ptr()) };
+                self.fingerprint = Some(fingerprint);
+                self.done = true;
+            }
+        }
+    }
+}
+
+impl Drop for CertificateFingerprint {
+    fn drop(&mut self) {
+        // SAFETY: The context was initialized by `mbedtls_sha256_init`
+        unsafe { mbedtls_sha256_free(&mut self.sha256) };
+    }
+}
+
+/// Initialize the TLS library with the hardware RNG.
+/// It must be called once, before any TLS session is opened.
+pub fn init_tls(rng: Rng) {
+    let rng = singleton!(: TlsRng = TlsRng(rng)).unwrap();
+    match Tls::new(rng) {
+        Ok(tls) => {
+            let tls = singleton!(: Tls<'static> = tls).unwrap();
+            TLS.lock(|v| v.borrow_mut().replace(tls.reference()));
+        }
+        Err(e) => log::error!("SYS | Could not initialize TLS: {:?}", e),
+    }
+}
+
+/// Get the reference to the TLS library needed to open a TLS session.
+/// Returns `None` if the library is not initialized.
+pub fn get_tls() -> Option<TlsReference<'static>> {
+    TLS.lock(|v| *v.borrow())
+}
+
+/// Convert a PEM file ending with a null byte into the string expected by the TLS library.
+pub const fn pem_file(file: &str) -> &CStr {
+    match CStr::from_bytes_with_nul(file.as_bytes()) {
+        Ok(v) => v,
+        Err(_) => panic!("PEM files must not contain null bytes"),
+    }
+}
+
+/// Parse a SHA-256 fingerprint written in hexadecimal, with or without colons between the bytes.
+pub fn parse_fingerprint(fingerprint: &str) -> Result<[u8; 32], ()> {
+    let mut digits = fingerprint.chars().filter(|c| *c != ':');
+    let mut bytes = [0u8; 32];
+    for byte in bytes.iter_mut() {
+        let high = digits.next().and_then(|c| c.to_digit(16)).ok_or(())?;
+        let low = digits.next().and_then(|c| c.to_digit(16)).ok_or(())?;
+        *byte = (high * 16 + low) as u8;
+    }
+    match digits.next() {
+        Some(_) => Err(()),
+        None => Ok(bytes),
+    }
+}
+
+/// The name of a server, sent to it and checked against its certificate.
+pub struct ServerName(String<SERVER_NAME_SIZE>);
+
+impl ServerName {
+    pub fn new(host: &str) -> Result<Self, ()> {
+        let mut name = String::new();
+        name.push_str(host)?;
+        name.push('\0')?;
+        match CStr::from_bytes_with_nul(name.as_bytes()) {
+            Ok(_) => Ok(Self(name)),
+            Err(_) => Err(()),
+        }
+    }
+
+    /// Get the name without the null byte.
+    pub fn as_str(&self) -> &str {
+        &self.0[..self.0.len() - 1]
+    }
+
+    fn as_c_str(&self) -> &CStr {
+        // The name was checked when it was created
+        CStr::from_bytes_with_nul(self.0.as_bytes()).unwrap()
+    }
+}
+
+/// Open a TLS session to a server on a connected socket and verify its certificate.
+/// The log messages are prefixed with `prefix`.
+pub async fn connect<'a, 's>(
+    socket: &'a mut TcpSocket<'s>,
+    server_name: &'a ServerName,
+    verification: &Verification<'a>,
+    prefix: &str,
+) -> Result<Session<'a, TcpStream<'a, 's>>, ()> {
+    let tls = get_tls().ok_or_else(|| log::error!("{} | TLS is not initialized", prefix))?;
+    let host = server_name.as_str();
+
+    let mut config = ClientSessionConfig::new();
+    config.server_name = Some(server_name.as_c_str());
+    let mut stream = TcpStream::new(socket);
+    match verification {
+        Verification::CaChain(ca_chain) => {
+            let ca_chain = Certificate::new(X509::PEM(ca_chain))
+                .map_err(|e| log::error!("{} | Invalid CA certificates: {:?}", prefix, e))?;
+            config.ca_chain = Some(ca_chain);
+        }
+        Verification::Fingerprint(_) => {
+            // The fingerprint replaces the verification by the certificate authorities
+            config.auth_mode = AuthMode::None;
+            config.max_version = Some(TlsVersion::Tls1_2);
+            stream.fingerprint = Some(Box::new(CertificateFingerprint::new()));
+        }
+    }
+
+    let mut session = Session::new(tls, stream, &SessionConfig::Client(config))
+        .map_err(|e| log::error!("{} | Could not create TLS session: {:?}", prefix, e))?;
+    session
+        .connect()
+        .await
+        .map_err(|e| log::error!("{} | TLS handshake with {} failed: {:?}", prefix, host, e))?;
+
+    if let Verification::Fingerprint(expected) = verification {
+        let fingerprint = session
+            .stream()
+            .fingerprint
+            .take()
+            .and_then(|v| v.fingerprint);
+        if fingerprint.as_ref() != Some(expected) {
+            log::error!(
+                "{} | Certificate of {} does not match the pinned fingerprint",
+                prefix,
+                host
+            );
+            return Err(());
+        }
+    }
+
+    log::info!("{} | TLS session opened with {}", prefix, host);
+    Ok(session)
+}
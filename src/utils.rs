@@ -1,55 +1,26 @@
-use core::str::FromStr;
+mod encoding;
+
+use core::net::Ipv6Addr;
 use embassy_futures::select::{Either, select};
 use embassy_net::{IpAddress, Stack, tcp::TcpSocket};
 use embassy_time::{Duration, Timer};
-use heapless::String;
-
-/// 12 bytes for the chars and 5 bytes for the colons
-pub const MAC_LEN: usize = 17;
-
-/// Converts the string representation of a MAC address to the correct format.
-/// (e.g. "00%3A00%3A00%3A00%3A00%3A00" -> "00:00:00:00:00:00")
-pub fn convert_mac_address(addr: &str) -> Result<String<MAC_LEN>, ()> {
-    // Check if the address is already in the correct format
-    if !addr.contains("%3A") {
-        return String::from_str(addr);
-    }
-
-    // Allocate a buffer to store the parsed address
-    let mut addr_parsed: String<MAC_LEN> = String::new();
-    let mut parts = addr.split("%3A");
-
-    // Iterate over the parts of the address and push them to the
-    // buffer, adding a colon between each part
-    let status = parts.try_for_each(|part| {
-        if addr_parsed.push_str(part).is_err() {
-            log::error!(
-                "Could not parse MAC address. Tried pushing {} in {}",
-                part,
-                addr_parsed
-            );
-            return Err(());
-        };
-
-        if addr_parsed.len() < addr_parsed.capacity() && addr_parsed.push(':').is_err() {
-            log::error!(
-                "Could not parse MAC address. Tried pushing \":\" in {}",
-                addr_parsed
-            );
-            return Err(());
-        }
-
-        Ok(())
-    });
+pub use encoding::{constant_time_eq, decode_base64, encode_base64, sha1};
 
-    match status {
-        Ok(_) => Ok(addr_parsed),
-        Err(_) => Err(()),
-    }
+/// Check if a configuration flag is enabled, which is the case when it is set to "true" or "1".
+pub fn is_flag_enabled(flag: &str) -> bool {
+    flag == "true" || flag == "1"
 }
 
 /// Parse an IP address from a string
 pub fn parse_ip_address(ip_str: &str) -> Result<IpAddress, &str> {
+    // IPv6 addresses are the only ones with colons
+    if ip_str.contains(':') {
+        return match ip_str.parse::<Ipv6Addr>() {
+            Ok(v) => Ok(IpAddress::Ipv6(v)),
+            Err(_) => Err("Could not parse IPv6 address, bad format"),
+        };
+    }
+
     // Take a string of the form "000.000.000.000" and return an IpAddress
     let mut ip_buf = [0u8; 4];
     let mut parts = ip_str.split('.');
@@ -68,12 +39,60 @@ pub fn parse_ip_address(ip_str: &str) -> Result<IpAddress, &str> {
         Ok(())
     });
 
+    if status.is_ok() && parts.next().is_some() {
+        return Err("Invalid IP address size");
+    }
+
     match status {
         Ok(_) => Ok(IpAddress::v4(ip_buf[0], ip_buf[1], ip_buf[2], ip_buf[3])),
         Err(e) => Err(e),
     }
 }
 
+/// Check if an IP address is reachable from the internet.
+/// Private, shared, loopback, link-local, documentation, multicast and reserved addresses are not.
+pub fn is_public_ip_address(ip: &IpAddress) -> bool {
+    let ip = match ip {
+        IpAddress::Ipv4(v) => v,
+        // Only the global unicast range is routed, minus the documentation prefix
+        IpAddress::Ipv6(v) => {
+            let [a, b, ..] = v.segments();
+            return a & 0xe000 == 0x2000 && !(a == 0x2001 && b == 0x0db8);
+        }
+    };
+    let [a, b, _, _] = ip.octets();
+    !(ip.is_unspecified()
+        || ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_documentation()
+        || ip.is_multicast()
+        || ip.is_broadcast()
+        // "This network", shared address space, IETF protocol assignments, benchmarking and reserved
+        || a == 0
+        || (a == 100 && (64..128).contains(&b))
+        || (a == 192 && b == 0 && ip.octets()[2] == 0)
+        || (a == 198 && (18..20).contains(&b))
+        || a >= 240)
+}
+
+/// Get the global IPv6 address of the device, if it has one.
+pub fn get_global_ipv6(stack: Stack<'_>) -> Option<Ipv6Addr> {
+    let address = stack.config_v6()?.address.address();
+    is_public_ip_address(&IpAddress::Ipv6(address)).then_some(address)
+}
+
+/// Get the signal strength of the wifi access point the device is connected to, in dBm.
+pub fn get_wifi_rssi() -> Option<i32> {
+    let mut rssi = 0;
+    // SAFETY: The function only writes the RSSI to the provided pointer
+    let status = unsafe { esp_wifi_sys::include::esp_wifi_sta_get_rssi(&mut rssi) };
+    if status != 0 {
+        return None;
+    }
+    Some(rssi)
+}
+
 /// Wait for the wifi device to connect to the network and until it gets an IP address
 pub async fn wait_for_connection(stack: Stack<'_>) {
     while !stack.is_link_up() {
@@ -90,12 +109,22 @@ pub async fn wait_for_connection(stack: Stack<'_>) {
 }
 
 /// Writes a buffer to a TCP socket.
-pub async fn write_tcp_buf(socket: &mut TcpSocket<'_>, mut buf: &[u8]) -> Result<(), ()> {
-    while !buf.is_empty() {
-        match socket.write(buf).await {
-            Ok(0) => log::warn!("SYS | TCP buffer writer wrote 0 bytes to the buffer"),
-            Ok(n) => buf = &buf[n..],
-            Err(_) => return Err(()),
+pub async fn write_tcp_buf(socket: &mut TcpSocket<'_>, buf: &[u8]) -> Result<(), ()> {
+    write_tcp_parts(socket, [buf]).await
+}
+
+/// Writes multiple buffers to a TCP socket one after the other, then flushes it.
+pub async fn write_tcp_parts<'a>(
+    socket: &mut TcpSocket<'_>,
+    parts: impl IntoIterator<Item = &'a [u8]>,
+) -> Result<(), ()> {
+    for mut buf in parts {
+        while !buf.is_empty() {
+            match socket.write(buf).await {
+                Ok(0) => log::warn!("SYS | TCP buffer writer wrote 0 bytes to the buffer"),
+                Ok(n) => buf = &buf[n..],
+                Err(_) => return Err(()),
+            }
         }
     }
 
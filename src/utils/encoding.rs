@@ -0,0 +1,210 @@
+/// Decodes a standard base64 string into the output buffer.
+/// Returns the number of decoded bytes.
+pub fn decode_base64(input: &str, output: &mut [u8]) -> Result<usize, ()> {
+    let input = input.trim_end_matches('=').as_bytes();
+    let mut len = 0;
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+
+    for &c in input {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return Err(()),
+        };
+
+        acc = (acc << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            *output.get_mut(len).ok_or(())? = (acc >> bits) as u8;
+            len += 1;
+        }
+    }
+
+    Ok(len)
+}
+
+/// Encodes bytes into a standard base64 string in the output buffer, with padding.
+/// Returns the number of encoded bytes.
+pub fn encode_base64(input: &[u8], output: &mut [u8]) -> Result<usize, ()> {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut len = 0;
+
+    for chunk in input.chunks(3) {
+        let acc = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |acc, (i, &v)| acc | (v as u32) << (16 - 8 * i));
+
+        for i in 0..4 {
+            let c = if i <= chunk.len() {
+                ALPHABET[(acc >> (18 - 6 * i) & 0x3F) as usize]
+            } else {
+                b'='
+            };
+            *output.get_mut(len).ok_or(())? = c;
+            len += 1;
+        }
+    }
+
+    Ok(len)
+}
+
+/// Computes the SHA-1 digest of the input.
+/// SHA-1 must not be used for security, it is only needed by protocols such as WebSocket.
+pub fn sha1(input: &[u8]) -> [u8; 20] {
+    let mut state: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    // The input is followed by a single 1 bit, zeros, and its length in bits
+    let bit_len = (input.len() as u64) * 8;
+    let padded_len = (input.len() + 8) / 64 * 64 + 64;
+    let byte_at = |i: usize| match i {
+        i if i < input.len() => input[i],
+        i if i == input.len() => 0x80,
+        i if i >= padded_len - 8 => (bit_len >> (8 * (padded_len - 1 - i))) as u8,
+        _ => 0,
+    };
+
+    for block in (0..padded_len).step_by(64) {
+        let mut w = [0u32; 80];
+        for (i, word) in w.iter_mut().take(16).enumerate() {
+            let at = block + i * 4;
+            *word = u32::from_be_bytes([
+                byte_at(at),
+                byte_at(at + 1),
+                byte_at(at + 2),
+                byte_at(at + 3),
+            ]);
+        }
+        for i in 16..80 {
+            w[i] = (w[i - 3] ^ w[i - 8] ^ w[i - 14] ^ w[i - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (i, word) in w.iter().enumerate() {
+            let (f, k) = match i {
+                0..20 => ((b & c) | (!b & d), 0x5A827999),
+                20..40 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..60 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _ => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a
+                .rotate_left(5)
+                .wrapping_add(f)
+                .wrapping_add(e)
+                .wrapping_add(k)
+                .wrapping_add(*word);
+            e = d;
+            d = c;
+            c = b.rotate_left(30);
+            b = a;
+            a = temp;
+        }
+
+        for (v, x) in state.iter_mut().zip([a, b, c, d, e]) {
+            *v = v.wrapping_add(x);
+        }
+    }
+
+    let mut digest = [0u8; 20];
+    for (chunk, v) in digest.chunks_mut(4).zip(state) {
+        chunk.copy_from_slice(&v.to_be_bytes());
+    }
+    digest
+}
+
+/// Compares two byte slices in constant time to avoid leaking secrets through timing.
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Encode bytes into a new base64 string.
+    fn encode(input: &[u8]) -> String {
+        let mut output = [0u8; 64];
+        let len = encode_base64(input, &mut output).unwrap();
+        String::from_utf8(output[..len].to_vec()).unwrap()
+    }
+
+    /// Decode a base64 string into new bytes.
+    fn decode(input: &str) -> Result<Vec<u8>, ()> {
+        let mut output = [0u8; 64];
+        let len = decode_base64(input, &mut output)?;
+        Ok(output[..len].to_vec())
+    }
+
+    /// Format a digest as lowercase hexadecimal.
+    fn hex(digest: &[u8]) -> String {
+        digest.iter().map(|v| format!("{:02x}", v)).collect()
+    }
+
+    #[test]
+    fn encodes_base64_with_padding() {
+        assert_eq!(encode(b""), "");
+        assert_eq!(encode(b"f"), "Zg==");
+        assert_eq!(encode(b"fo"), "Zm8=");
+        assert_eq!(encode(b"foo"), "Zm9v");
+        assert_eq!(encode(b"foobar"), "Zm9vYmFy");
+        assert_eq!(encode(&[0xfb, 0xff]), "+/8=");
+    }
+
+    #[test]
+    fn decodes_base64() {
+        assert_eq!(decode("Zg==").unwrap(), b"f");
+        assert_eq!(decode("Zm8=").unwrap(), b"fo");
+        assert_eq!(decode("Zm9vYmFy").unwrap(), b"foobar");
+        assert_eq!(decode("+/8=").unwrap(), [0xfb, 0xff]);
+        assert_eq!(decode("dXNlcjpwYXNz").unwrap(), b"user:pass");
+    }
+
+    #[test]
+    fn rejects_invalid_base64() {
+        assert_eq!(decode("Zm9v YmFy"), Err(()));
+        assert_eq!(decode("Zm9v-_"), Err(()));
+    }
+
+    #[test]
+    fn rejects_small_output_buffers() {
+        let mut output = [0u8; 3];
+        assert_eq!(encode_base64(b"foo", &mut output), Err(()));
+        assert_eq!(decode_base64("Zm9vYg==", &mut output), Err(()));
+    }
+
+    #[test]
+    fn computes_sha1() {
+        assert_eq!(hex(&sha1(b"")), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+        assert_eq!(
+            hex(&sha1(b"abc")),
+            "a9993e364706816aba3e25717850c26c9cd0d89d"
+        );
+        // The padding of this input spans two blocks
+        assert_eq!(
+            hex(&sha1(
+                b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"
+            )),
+            "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+        );
+        assert_eq!(
+            hex(&sha1(&[b'a'; 1000])),
+            "291e9a6c66994949b57ba5e650361e98fc36b1ba"
+        );
+    }
+
+    #[test]
+    fn compares_in_constant_time() {
+        assert!(constant_time_eq(b"secret", b"secret"));
+        assert!(!constant_time_eq(b"secret", b"secreT"));
+        assert!(!constant_time_eq(b"secret", b"secrets"));
+        assert!(constant_time_eq(b"", b""));
+    }
+}